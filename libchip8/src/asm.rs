@@ -0,0 +1,752 @@
+//! Assembler
+//!
+//! Accepts the mnemonics printed by the disassembler, plus:
+//!
+//! * `name:` labels the current address
+//! * `define NAME value` defines a constant
+//! * `db` and `dw` emit bytes and big endian words
+//! * `include "file"` assembles another file in place
+//! * `macro name a, b` ... `endm` defines a macro, called like an instruction
+//!
+//! Numbers are decimal, hexadecimal (`0x1F` or `$1F`) or binary (`0b101` or `%101`). Values can
+//! add and subtract numbers, labels and constants. Comments start with `;`.
+
+use super::{Instruction, PROGRAM_START_ADDRESS};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum nesting of includes and macro calls
+const MAX_DEPTH: usize = 16;
+
+/// An assembly error and where in the source it was found. Lines and columns start at 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+/// Assemble `source` into a ROM loaded at the program start address. Includes are relative to
+/// the current directory
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new();
+    assembler.expand(source, "<input>", Path::new("."), None, 0)?;
+    assembler.emit()
+}
+
+/// Assemble a source file. Includes are relative to the including file
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AsmError> {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: name.clone(),
+        line: 0,
+        column: 0,
+        message: e.to_string()
+    })?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut assembler = Assembler::new();
+    assembler.expand(&source, &name, dir, None, 0)?;
+    assembler.emit()
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: String,
+    line: usize,
+    column: usize
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    text: String,
+    location: Location
+}
+
+enum Item {
+    Label(String),
+    Define(String, Operand),
+    Statement(String, Vec<Operand>)
+}
+
+#[derive(Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: String,
+    file: String,
+    dir: PathBuf
+}
+
+/// An operand classified for instruction encoding
+enum Arg {
+    Reg(usize),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    B,
+    HF,
+    R,
+    Pitch,
+    Long(Operand),
+    Value(Operand)
+}
+
+/// A line split into label, mnemonic and the text after the mnemonic, with their columns
+struct SourceLine<'s> {
+    label: Option<(usize, &'s str)>,
+    mnemonic: Option<(usize, &'s str)>,
+    rest: (usize, &'s str)
+}
+
+struct Assembler {
+    items: Vec<(Location, Item)>,
+    macros: HashMap<String, Macro>
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Assembler {
+            items: Vec::new(),
+            macros: HashMap::new()
+        }
+    }
+
+    /// Parse `source` into items, expanding includes and macros. Inside a macro call every
+    /// location is reported at the call
+    fn expand(&mut self, source: &str, file: &str, dir: &Path, call: Option<&Location>, depth: usize) -> Result<(), AsmError> {
+        let mut lines = source.lines().enumerate();
+
+        while let Some((index, text)) = lines.next() {
+            let locate = |column: usize| match call {
+                Some(location) => location.clone(),
+                None => Location {
+                    file: file.to_string(),
+                    line: index + 1,
+                    column: column
+                }
+            };
+
+            let line = parse_line(text);
+
+            if let Some((column, name)) = line.label {
+                if !is_identifier(name) || register(name).is_some() {
+                    return Err(locate(column).error(format!("Invalid label name '{}'", name)));
+                }
+
+                self.items.push((locate(column), Item::Label(name.to_string())));
+            }
+
+            let (column, mnemonic) = match line.mnemonic {
+                Some(mnemonic) => mnemonic,
+                None => continue
+            };
+
+            let location = locate(column);
+            let (rest_column, rest) = line.rest;
+
+            match mnemonic.to_lowercase().as_str() {
+                "define" => {
+                    let (name, value) = match next_word(rest, 0) {
+                        Some((start, name)) => (name, split_operands(&rest[(start + name.len())..], rest_column + start + name.len(), &locate)),
+                        None => return Err(location.error("Expected a constant name"))
+                    };
+
+                    if !is_identifier(name) || register(name).is_some() {
+                        return Err(location.error(format!("Invalid constant name '{}'", name)));
+                    }
+
+                    // the value is everything after the name
+                    let value = match value.len() {
+                        1 if !value[0].text.is_empty() => value[0].clone(),
+                        _ => return Err(location.error(format!("Expected a value for '{}'", name)))
+                    };
+
+                    self.items.push((location, Item::Define(name.to_string(), value)));
+                },
+                "include" => {
+                    if rest.len() < 2 || !rest.starts_with('"') || !rest.ends_with('"') {
+                        return Err(locate(rest_column).error("Expected a quoted file name"));
+                    }
+
+                    if depth >= MAX_DEPTH {
+                        return Err(location.error("Includes are nested too deeply"));
+                    }
+
+                    let path = dir.join(&rest[1..(rest.len() - 1)]);
+                    let source = fs::read_to_string(&path).map_err(
+                        |e| location.error(format!("Could not read '{}': {}", path.display(), e))
+                    )?;
+                    let include_dir = path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+
+                    self.expand(&source, &path.display().to_string(), &include_dir, call, depth + 1)?;
+                },
+                "macro" => {
+                    let (name, params) = match next_word(rest, 0) {
+                        Some((start, name)) => (name, split_operands(&rest[(start + name.len())..], 0, &locate)),
+                        None => return Err(location.error("Expected a macro name"))
+                    };
+
+                    if !is_identifier(name) || is_mnemonic(&name.to_uppercase()) || self.macros.contains_key(&name.to_lowercase()) {
+                        return Err(location.error(format!("Invalid macro name '{}'", name)));
+                    }
+
+                    let params: Vec<String> = params.into_iter().map(|p| p.text).filter(|p| !p.is_empty()).collect();
+
+                    if let Some(param) = params.iter().find(|p| !is_identifier(p)) {
+                        return Err(location.error(format!("Invalid macro parameter '{}'", param)));
+                    }
+
+                    let mut body = Vec::new();
+                    let mut ended = false;
+
+                    for (_, text) in lines.by_ref() {
+                        match parse_line(text).mnemonic {
+                            Some((_, m)) if m.eq_ignore_ascii_case("endm") => {
+                                ended = true;
+                                break;
+                            },
+                            Some((_, m)) if m.eq_ignore_ascii_case("macro") => {
+                                return Err(location.error(format!("Macro '{}' contains a macro definition", name)));
+                            },
+                            _ => body.push(text)
+                        }
+                    }
+
+                    if !ended {
+                        return Err(location.error(format!("Macro '{}' is missing endm", name)));
+                    }
+
+                    self.macros.insert(name.to_lowercase(), Macro {
+                        params: params,
+                        body: body.join("\n"),
+                        file: file.to_string(),
+                        dir: dir.to_path_buf()
+                    });
+                },
+                "endm" => {
+                    return Err(location.error("endm without macro"));
+                },
+                name => {
+                    let operands = split_operands(rest, rest_column, &locate);
+
+                    let m = match self.macros.get(name) {
+                        Some(m) => m.clone(),
+                        None => {
+                            self.items.push((location, Item::Statement(name.to_uppercase(), operands)));
+                            continue;
+                        }
+                    };
+
+                    let args: Vec<Operand> = operands.into_iter().filter(|o| !o.text.is_empty()).collect();
+
+                    if args.len() != m.params.len() {
+                        return Err(location.error(format!("Macro '{}' expects {} arguments, found {}", mnemonic, m.params.len(), args.len())));
+                    }
+
+                    if depth >= MAX_DEPTH {
+                        return Err(location.error("Macro calls are nested too deeply"));
+                    }
+
+                    let body = substitute(&m.body, &m.params, &args);
+                    self.expand(&body, &m.file, &m.dir, Some(&location), depth + 1)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Assign addresses to labels, then encode every statement
+    fn emit(&self) -> Result<Vec<u8>, AsmError> {
+        let mut symbols = HashMap::new();
+        let mut addr = PROGRAM_START_ADDRESS as i64;
+
+        for (location, item) in self.items.iter() {
+            let (name, value) = match *item {
+                Item::Label(ref name) => (name, addr),
+                Item::Define(ref name, ref value) => (name, evaluate(value, &symbols)?),
+                Item::Statement(ref mnemonic, ref operands) => {
+                    addr += statement_size(mnemonic, operands);
+                    continue;
+                }
+            };
+
+            if symbols.insert(name.clone(), value).is_some() {
+                return Err(location.error(format!("'{}' is already defined", name)));
+            }
+        }
+
+        let mut rom = Vec::new();
+
+        for (location, item) in self.items.iter() {
+            let (mnemonic, operands) = match *item {
+                Item::Statement(ref mnemonic, ref operands) => (mnemonic, operands),
+                _ => continue
+            };
+
+            match mnemonic.as_str() {
+                "DB" => {
+                    for operand in operands.iter() {
+                        rom.push(value(operand, &symbols, -0x80, 0xFF)? as u8);
+                    }
+                },
+                "DW" => {
+                    for operand in operands.iter() {
+                        let word = value(operand, &symbols, -0x8000, 0xFFFF)?;
+                        rom.push((word >> 8) as u8);
+                        rom.push(word as u8);
+                    }
+                },
+                _ => {
+                    let instr = instruction(mnemonic, operands, &symbols, location)?;
+                    rom.extend(instr.encode());
+                }
+            }
+        }
+
+        Ok(rom)
+    }
+}
+
+fn instruction(mnemonic: &str, operands: &[Operand], symbols: &HashMap<String, i64>, location: &Location) -> Result<Instruction, AsmError> {
+    let args = classify(mnemonic, operands)?;
+
+    let address = |o: &Operand| value(o, symbols, 0, 0xFFF).map(|v| v as u16);
+    let byte = |o: &Operand| value(o, symbols, -0x80, 0xFF).map(|v| v as u8);
+    let nibble = |o: &Operand| value(o, symbols, 0, 0xF).map(|v| v as u8);
+
+    let instr = match (mnemonic, &args[..]) {
+        ("CLS", []) => Instruction::CLS(),
+        ("RET", []) => Instruction::RET(),
+        ("JP", [Arg::Value(a)]) => Instruction::JP(address(a)?),
+        ("JP", [Arg::Reg(0), Arg::Value(a)]) => Instruction::JR(address(a)?),
+        ("CALL", [Arg::Value(a)]) => Instruction::CALL(address(a)?),
+        ("SE", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::SEVXY(*x, *y),
+        ("SE", [Arg::Reg(x), Arg::Value(b)]) => Instruction::SEVXB(*x, byte(b)?),
+        ("SNE", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::SNEVXY(*x, *y),
+        ("SNE", [Arg::Reg(x), Arg::Value(b)]) => Instruction::SNEVXB(*x, byte(b)?),
+        ("LD", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::LDVXY(*x, *y),
+        ("LD", [Arg::Reg(x), Arg::Value(b)]) => Instruction::LDVXB(*x, byte(b)?),
+        ("LD", [Arg::Reg(x), Arg::DT]) => Instruction::LDVXDT(*x),
+        ("LD", [Arg::Reg(x), Arg::K]) => Instruction::LDVXK(*x),
+        ("LD", [Arg::Reg(x), Arg::IndirectI]) => Instruction::LDVXI(*x),
+        ("LD", [Arg::Reg(x), Arg::R]) => Instruction::LDVXR(*x),
+        ("LD", [Arg::I, Arg::Value(a)]) => Instruction::LDI(address(a)?),
+        ("LD", [Arg::I, Arg::Long(a)]) => Instruction::LDILONG(value(a, symbols, 0, 0xFFFF)? as u16),
+        ("LD", [Arg::DT, Arg::Reg(x)]) => Instruction::LDDTVX(*x),
+        ("LD", [Arg::ST, Arg::Reg(x)]) => Instruction::LDSTVX(*x),
+        ("LD", [Arg::F, Arg::Reg(x)]) => Instruction::LDFVX(*x),
+        ("LD", [Arg::HF, Arg::Reg(x)]) => Instruction::LDHFVX(*x),
+        ("LD", [Arg::B, Arg::Reg(x)]) => Instruction::LDB(*x),
+        ("LD", [Arg::IndirectI, Arg::Reg(x)]) => Instruction::LDIVX(*x),
+        ("LD", [Arg::R, Arg::Reg(x)]) => Instruction::LDRVX(*x),
+        ("LD", [Arg::Pitch, Arg::Reg(x)]) => Instruction::PITCH(*x),
+        ("ADD", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::ADDVXY(*x, *y),
+        ("ADD", [Arg::Reg(x), Arg::Value(b)]) => Instruction::ADDVXB(*x, byte(b)?),
+        ("ADD", [Arg::I, Arg::Reg(x)]) => Instruction::ADDIVX(*x),
+        ("OR", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::ORVXY(*x, *y),
+        ("AND", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::ANDVXY(*x, *y),
+        ("XOR", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::XORVXY(*x, *y),
+        ("SUB", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::SUBVXY(*x, *y),
+        ("SUBN", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::SUBNVXY(*x, *y),
+        // a single register shifts itself, whichever register the quirks shift
+        ("SHR", [Arg::Reg(x)]) => Instruction::SHR(*x, *x),
+        ("SHR", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::SHR(*x, *y),
+        ("SHL", [Arg::Reg(x)]) => Instruction::SHL(*x, *x),
+        ("SHL", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::SHL(*x, *y),
+        ("RND", [Arg::Reg(x), Arg::Value(b)]) => Instruction::RND(*x, byte(b)?),
+        ("DRW", [Arg::Reg(x), Arg::Reg(y), Arg::Value(n)]) => Instruction::DRAW(*x, *y, nibble(n)?),
+        ("SKP", [Arg::Reg(x)]) => Instruction::SKP(*x),
+        ("SKNP", [Arg::Reg(x)]) => Instruction::SKNP(*x),
+        ("SCD", [Arg::Value(n)]) => Instruction::SCD(nibble(n)?),
+        ("SCU", [Arg::Value(n)]) => Instruction::SCU(nibble(n)?),
+        ("SCR", []) => Instruction::SCR(),
+        ("SCL", []) => Instruction::SCL(),
+        ("EXIT", []) => Instruction::EXIT(),
+        ("LOW", []) => Instruction::LOW(),
+        ("HIGH", []) => Instruction::HIGH(),
+        ("SAVE", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::SAVE(*x, *y),
+        ("LOAD", [Arg::Reg(x), Arg::Reg(y)]) => Instruction::LOAD(*x, *y),
+        ("PLANE", [Arg::Value(n)]) => Instruction::PLANE(value(n, symbols, 0, 3)? as u8),
+        ("AUDIO", []) => Instruction::AUDIO(),
+        _ if is_mnemonic(mnemonic) => return Err(location.error(format!("Invalid operands for {}", mnemonic))),
+        _ => return Err(location.error(format!("Unknown instruction '{}'", mnemonic)))
+    };
+
+    Ok(instr)
+}
+
+/// Classify the operands of an instruction. `SAVE` and `LOAD` take a `Vx - Vy` range
+fn classify(mnemonic: &str, operands: &[Operand]) -> Result<Vec<Arg>, AsmError> {
+    if (mnemonic == "SAVE" || mnemonic == "LOAD") && operands.len() == 1 {
+        let operand = &operands[0];
+        let range: Vec<Option<usize>> = operand.text.split('-').map(|r| register(r.trim())).collect();
+
+        return match range[..] {
+            [Some(x), Some(y)] => Ok(vec![Arg::Reg(x), Arg::Reg(y)]),
+            _ => Err(operand.location.error(format!("Expected a register range, found '{}'", operand.text)))
+        };
+    }
+
+    let mut args = Vec::new();
+
+    for operand in operands.iter() {
+        if operand.text.is_empty() {
+            return Err(operand.location.error("Expected an operand"));
+        }
+
+        let arg = match operand.text.to_uppercase().as_str() {
+            "I" => Arg::I,
+            "[I]" => Arg::IndirectI,
+            "DT" => Arg::DT,
+            "ST" => Arg::ST,
+            "K" => Arg::K,
+            "F" => Arg::F,
+            "B" => Arg::B,
+            "HF" => Arg::HF,
+            "R" => Arg::R,
+            "PITCH" => Arg::Pitch,
+            text => match register(text) {
+                Some(x) => Arg::Reg(x),
+                None => match long_operand(operand) {
+                    Some(operand) => Arg::Long(operand),
+                    None => Arg::Value(operand.clone())
+                }
+            }
+        };
+
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+/// The value of a `LONG addr` operand
+fn long_operand(operand: &Operand) -> Option<Operand> {
+    let text = &operand.text;
+
+    // other operands can have multibyte characters at any index
+    let is_long = text.get(..4).map_or(false, |prefix| prefix.eq_ignore_ascii_case("long"));
+
+    if !is_long || !text[4..].starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let value = text[4..].trim_start();
+    let mut location = operand.location.clone();
+    location.column += text.len() - value.len();
+
+    Some(Operand {
+        text: value.to_string(),
+        location: location
+    })
+}
+
+/// Size in bytes of a statement, known before any labels are
+fn statement_size(mnemonic: &str, operands: &[Operand]) -> i64 {
+    match mnemonic {
+        "DB" => operands.len() as i64,
+        "DW" => 2 * operands.len() as i64,
+        "LD" if operands.len() == 2 && long_operand(&operands[1]).is_some() => 4,
+        _ => 2
+    }
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    let mnemonics = [
+        "CLS", "RET", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN", "SHR", "SHL",
+        "RND", "DRW", "SKP", "SKNP", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SAVE", "LOAD", "PLANE", "AUDIO"
+    ];
+
+    mnemonics.contains(&mnemonic)
+}
+
+/// Evaluate an operand and check it is in `min..=max`
+fn value(operand: &Operand, symbols: &HashMap<String, i64>, min: i64, max: i64) -> Result<i64, AsmError> {
+    let value = evaluate(operand, symbols)?;
+
+    if value < min || value > max {
+        return Err(operand.location.error(format!("Value {} is out of range {} to {}", value, min, max)));
+    }
+
+    Ok(value)
+}
+
+/// Evaluate a sum of numbers and symbols
+fn evaluate(operand: &Operand, symbols: &HashMap<String, i64>) -> Result<i64, AsmError> {
+    let text = &operand.text;
+    let error_at = |offset: usize, message: String| {
+        let mut location = operand.location.clone();
+        location.column += offset;
+        location.error(message)
+    };
+
+    let mut chars = text.char_indices().peekable();
+    let mut total: i64 = 0;
+    let mut sign = 1;
+    let mut expect_term = true;
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '+' || c == '-' {
+            if c == '-' {
+                sign = -sign;
+            }
+
+            chars.next();
+            expect_term = true;
+            continue;
+        }
+
+        if !expect_term {
+            return Err(error_at(start, "Expected + or -".to_string()));
+        }
+
+        let mut end = text.len();
+
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() || c == '+' || c == '-' {
+                end = i;
+                break;
+            }
+
+            chars.next();
+        }
+
+        let term = &text[start..end];
+
+        let value = match parse_number(term).or_else(|| symbols.get(term).cloned()) {
+            Some(value) => value,
+            None if term.starts_with(|c: char| c.is_ascii_digit() || c == '$' || c == '%') => {
+                return Err(error_at(start, format!("Invalid number '{}'", term)));
+            },
+            None => return Err(error_at(start, format!("Undefined symbol '{}'", term)))
+        };
+
+        total = value.checked_mul(sign).and_then(|value| total.checked_add(value)).ok_or_else(
+            || error_at(start, format!("Value '{}' is out of range", text))
+        )?;
+        sign = 1;
+        expect_term = false;
+    }
+
+    if expect_term {
+        return Err(error_at(text.len(), "Expected a value".to_string()));
+    }
+
+    Ok(total)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let lower = text.to_lowercase();
+
+    if lower.starts_with("0x") {
+        i64::from_str_radix(&text[2..], 16).ok()
+    }
+    else if lower.starts_with("0b") {
+        i64::from_str_radix(&text[2..], 2).ok()
+    }
+    else if text.starts_with('$') {
+        i64::from_str_radix(&text[1..], 16).ok()
+    }
+    else if text.starts_with('%') {
+        i64::from_str_radix(&text[1..], 2).ok()
+    }
+    else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse().ok()
+    }
+    else {
+        None
+    }
+}
+
+/// Register index of `V0` to `VF`
+fn register(text: &str) -> Option<usize> {
+    if text.len() == 2 && text.starts_with(|c: char| c == 'V' || c == 'v') {
+        usize::from_str_radix(&text[1..], 16).ok()
+    }
+    else {
+        None
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_') && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn parse_line<'s>(text: &'s str) -> SourceLine<'s> {
+    // strip the comment, a ; in a quoted file name does not start one
+    let mut quoted = false;
+    let end = text.char_indices()
+        .find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == ';' && !quoted
+        })
+        .map_or(text.len(), |(i, _)| i);
+    let code = &text[..end];
+
+    let mut line = SourceLine {
+        label: None,
+        mnemonic: None,
+        rest: (code.len() + 1, "")
+    };
+
+    let mut pos = 0;
+
+    if let Some((start, word)) = next_word(code, 0) {
+        if word.ends_with(':') {
+            line.label = Some((start + 1, &word[..(word.len() - 1)]));
+            pos = start + word.len();
+        }
+    }
+
+    if let Some((start, word)) = next_word(code, pos) {
+        let after = start + word.len();
+        let rest = code[after..].trim_start();
+        let rest_start = code.len() - rest.len();
+
+        line.mnemonic = Some((start + 1, word));
+        line.rest = (rest_start + 1, rest.trim_end());
+    }
+
+    line
+}
+
+/// The next whitespace separated word at or after `pos`, and its offset
+fn next_word(text: &str, pos: usize) -> Option<(usize, &str)> {
+    let rest = &text[pos..];
+    let trimmed = rest.trim_start();
+
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let start = pos + rest.len() - trimmed.len();
+    let end = trimmed.find(char::is_whitespace).unwrap_or_else(|| trimmed.len());
+
+    Some((start, &trimmed[..end]))
+}
+
+/// Split comma separated operands, `column` is the column of the start of `text`
+fn split_operands<F>(text: &str, column: usize, locate: &F) -> Vec<Operand>
+    where F: Fn(usize) -> Location
+{
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut operands = Vec::new();
+    let mut offset = 0;
+
+    for part in text.split(',') {
+        let trimmed = part.trim_start();
+
+        operands.push(Operand {
+            text: trimmed.trim_end().to_string(),
+            location: locate(column + offset + part.len() - trimmed.len())
+        });
+
+        offset += part.len() + 1;
+    }
+
+    operands
+}
+
+/// Replace whole word macro parameters with the call's arguments
+fn substitute(body: &str, params: &[String], args: &[Operand]) -> String {
+    let mut out = String::new();
+    let mut word = String::new();
+
+    let flush = |word: &mut String, out: &mut String| {
+        match params.iter().position(|p| p == word) {
+            Some(i) => out.push_str(&args[i].text),
+            None => out.push_str(word)
+        }
+        word.clear();
+    };
+
+    for c in body.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+        }
+        else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+
+    flush(&mut word, &mut out);
+
+    out
+}
+
+impl Location {
+    fn error<S: Into<String>>(&self, message: S) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.line,
+            column: self.column,
+            message: message.into()
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        }
+        else {
+            write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+        }
+    }
+}
+
+impl Error for AsmError {
+    fn description(&self) -> &str {
+        "assembly failed"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("42"), Some(42));
+        assert_eq!(parse_number("0x2A"), Some(42));
+        assert_eq!(parse_number("$2a"), Some(42));
+        assert_eq!(parse_number("0b101010"), Some(42));
+        assert_eq!(parse_number("%101010"), Some(42));
+        assert_eq!(parse_number("label"), None);
+    }
+
+    #[test]
+    fn test_substitute() {
+        let arg = |text: &str| Operand {
+            text: text.to_string(),
+            location: Location { file: String::new(), line: 1, column: 1 }
+        };
+        let params = vec!["x".to_string(), "n".to_string()];
+
+        assert_eq!(substitute("ADD x, n ; xn", &params, &[arg("V1"), arg("2")]), "ADD V1, 2 ; xn");
+    }
+}
@@ -0,0 +1,290 @@
+//! Breakpoints, watchpoints and stepping for a `Chip8`
+
+use super::{Chip8, Chip8Error};
+
+use std::collections::{HashMap, HashSet};
+
+/// Upper bound on the instructions run by `step_over` and `step_out` before giving up
+const MAX_RUN_STEPS: usize = 1000000;
+
+/// Kind of a data memory access
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write
+}
+
+/// A data memory access made by an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryAccess {
+    pub addr: u16,
+    pub access: Access
+}
+
+/// Accesses that trigger a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Watch {
+    Read,
+    Write,
+    ReadWrite
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater
+}
+
+/// Breaks when a register comparison becomes true
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisterCondition {
+    pub register: usize,
+    pub comparison: Comparison,
+    pub value: u8
+}
+
+/// Why execution stopped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// A single step completed
+    Step,
+    /// The instruction at the address is about to be executed
+    Breakpoint(u16),
+    /// The last instruction accessed a watched address
+    Watchpoint(MemoryAccess),
+    /// The register condition with the given index became true
+    Condition(usize),
+    /// The machine executed EXIT
+    Halted,
+    /// Too many instructions were run without reaching the target
+    StepLimit
+}
+
+/// Debugger for a `Chip8` virtual machine
+pub struct Debugger {
+    breakpoints: HashSet<u16>,
+    watchpoints: HashMap<u16, Watch>,
+    conditions: Vec<(RegisterCondition, bool)>, // condition and its last value
+    resume_from: Option<u16>                    // breakpoint to run past when resuming
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: HashSet::new(),
+            watchpoints: HashMap::new(),
+            conditions: Vec::new(),
+            resume_from: None
+        }
+    }
+
+    /// Enable the memory tracking the debugger needs for watchpoints
+    pub fn attach(&mut self, vm: &mut Chip8) {
+        vm.set_memory_tracking(true);
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn get_breakpoints(&self) -> Vec<u16> {
+        let mut breakpoints: Vec<u16> = self.breakpoints.iter().cloned().collect();
+        breakpoints.sort();
+
+        breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, addr: u16, watch: Watch) {
+        self.watchpoints.insert(addr, watch);
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: u16) -> bool {
+        self.watchpoints.remove(&addr).is_some()
+    }
+
+    pub fn get_watchpoints(&self) -> Vec<(u16, Watch)> {
+        let mut watchpoints: Vec<(u16, Watch)> = self.watchpoints.iter().map(|(a, w)| (*a, *w)).collect();
+        watchpoints.sort_by_key(|&(addr, _)| addr);
+
+        watchpoints
+    }
+
+    /// Add a register condition, returns its index
+    pub fn add_condition(&mut self, condition: RegisterCondition) -> usize {
+        self.conditions.push((condition, false));
+        self.conditions.len() - 1
+    }
+
+    pub fn remove_condition(&mut self, index: usize) -> bool {
+        if index < self.conditions.len() {
+            self.conditions.remove(index);
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    pub fn get_conditions(&self) -> Vec<RegisterCondition> {
+        self.conditions.iter().map(|&(c, _)| c).collect()
+    }
+
+    /// Execute a single instruction, ignoring breakpoints
+    pub fn step(&mut self, vm: &mut Chip8) -> Result<StopReason, Chip8Error> {
+        self.resume_from = None;
+
+        vm.step()?;
+
+        Ok(self.check_after_step(vm).unwrap_or(StopReason::Step))
+    }
+
+    /// Execute the next instruction, running a called subroutine until it returns
+    pub fn step_over(&mut self, vm: &mut Chip8) -> Result<StopReason, Chip8Error> {
+        let pc = vm.get_pc();
+
+        if vm.read_word(pc) & 0xF000 != 0x2000 {
+            return self.step(vm);
+        }
+
+        let sp = vm.get_sp();
+        self.run_until(vm, MAX_RUN_STEPS, |vm| vm.get_pc() == pc + 2 && vm.get_sp() == sp)
+    }
+
+    /// Run until the current subroutine returns
+    pub fn step_out(&mut self, vm: &mut Chip8) -> Result<StopReason, Chip8Error> {
+        let sp = vm.get_sp();
+
+        if sp == 0 {
+            return self.step(vm);
+        }
+
+        self.run_until(vm, MAX_RUN_STEPS, |vm| vm.get_sp() < sp)
+    }
+
+    /// Run up to `max_steps` instructions without waiting, until something stops execution
+    pub fn run(&mut self, vm: &mut Chip8, max_steps: usize) -> Result<StopReason, Chip8Error> {
+        self.run_until(vm, max_steps, |_| false)
+    }
+
+    /// Update the machine like `Chip8::update`, stopping on breakpoints, watchpoints and conditions
+    pub fn update(&mut self, vm: &mut Chip8) -> Result<Option<StopReason>, Chip8Error> {
+        for _ in 0..vm.cycles_due() {
+            if let Some(reason) = self.run_instruction(vm)? {
+                return Ok(Some(reason));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Run the rest of the current frame like `Chip8::run_frame`, stopping on breakpoints,
+    /// watchpoints and conditions
+    pub fn run_frame(&mut self, vm: &mut Chip8) -> Result<Option<StopReason>, Chip8Error> {
+        for _ in 0..vm.frame_cycles_left() {
+            if let Some(reason) = self.run_instruction(vm)? {
+                return Ok(Some(reason));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn run_until<F>(&mut self, vm: &mut Chip8, max_steps: usize, done: F) -> Result<StopReason, Chip8Error>
+        where F: Fn(&Chip8) -> bool
+    {
+        // the first instruction always runs, even if there is a breakpoint on it
+        self.resume_from = Some(vm.get_pc());
+
+        for _ in 0..max_steps {
+            if let Some(reason) = self.run_instruction(vm)? {
+                return Ok(reason);
+            }
+
+            if done(vm) {
+                return Ok(StopReason::Step);
+            }
+        }
+
+        Ok(StopReason::StepLimit)
+    }
+
+    /// Execute the instruction at the program counter unless a breakpoint is set on it
+    fn run_instruction(&mut self, vm: &mut Chip8) -> Result<Option<StopReason>, Chip8Error> {
+        let pc = vm.get_pc();
+
+        if vm.is_halted() {
+            return Ok(Some(StopReason::Halted));
+        }
+
+        if self.breakpoints.contains(&pc) && self.resume_from != Some(pc) {
+            // continuing from here will run past the breakpoint
+            self.resume_from = Some(pc);
+            return Ok(Some(StopReason::Breakpoint(pc)));
+        }
+
+        self.resume_from = None;
+
+        vm.step()?;
+
+        Ok(self.check_after_step(vm))
+    }
+
+    fn check_after_step(&mut self, vm: &Chip8) -> Option<StopReason> {
+        for access in vm.get_memory_accesses() {
+            let triggered = match (self.watchpoints.get(&access.addr), access.access) {
+                (Some(&Watch::ReadWrite), _) => true,
+                (Some(&Watch::Read), Access::Read) => true,
+                (Some(&Watch::Write), Access::Write) => true,
+                _ => false
+            };
+
+            if triggered {
+                return Some(StopReason::Watchpoint(*access));
+            }
+        }
+
+        // conditions only trigger when they change from false to true
+        let mut reason = None;
+
+        for (index, &mut (condition, ref mut last)) in self.conditions.iter_mut().enumerate() {
+            let current = condition.is_met(vm);
+
+            if current && !*last && reason.is_none() {
+                reason = Some(StopReason::Condition(index));
+            }
+
+            *last = current;
+        }
+
+        if reason.is_none() && vm.is_halted() {
+            reason = Some(StopReason::Halted);
+        }
+
+        reason
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
+}
+
+impl RegisterCondition {
+    pub fn is_met(&self, vm: &Chip8) -> bool {
+        let register = vm.get_register(self.register);
+
+        match self.comparison {
+            Comparison::Equal => register == self.value,
+            Comparison::NotEqual => register != self.value,
+            Comparison::Less => register < self.value,
+            Comparison::Greater => register > self.value
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Disassembler
+
+use super::Instruction;
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Decode `bytes`, loaded at `origin`, into instructions. Words that are not valid opcodes are skipped
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<(u16, Instruction)> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset + 1 < bytes.len() {
+        let addr = origin.wrapping_add(offset as u16);
+
+        match Instruction::decode(word(bytes, offset), word(bytes, offset + 2)) {
+            Ok(instr) => {
+                instructions.push((addr, instr));
+                offset += instr.size() as usize;
+            },
+            Err(_) => {
+                offset += 2;
+            }
+        }
+    }
+
+    instructions
+}
+
+/// Disassemble `bytes` into a listing with labels on jump and call targets.
+/// Words that are not valid opcodes, or would assemble to other bytes, are listed as `dw` data
+pub fn listing(bytes: &[u8], origin: u16) -> String {
+    let lines = listing_lines(bytes, origin);
+
+    // only label targets that start a line of the listing
+    let labels: BTreeSet<u16> = lines.iter()
+        .filter_map(|&(_, line)| match line {
+            Line::Code(instr) => jump_target(instr),
+            _ => None
+        })
+        .filter(|target| lines.iter().any(|&(addr, _)| addr == *target))
+        .collect();
+
+    let mut out = String::new();
+
+    for (addr, line) in lines {
+        if labels.contains(&addr) {
+            out.push_str(&format!("{}:\n", label(addr)));
+        }
+
+        let text = match line {
+            Line::Code(instr) => match jump_target(instr).filter(|target| labels.contains(target)) {
+                Some(target) => match instr {
+                    Instruction::JP(_) => format!("JP {}", label(target)),
+                    Instruction::CALL(_) => format!("CALL {}", label(target)),
+                    _ => format!("JP V0, {}", label(target))
+                },
+                None => instr.to_string()
+            },
+            Line::Word(w) => format!("dw 0x{:04X}", w),
+            Line::Byte(b) => format!("db 0x{:02X}", b)
+        };
+
+        out.push_str(&format!("    {:<20} ; {:03X}\n", text, addr));
+    }
+
+    out
+}
+
+#[derive(Clone, Copy)]
+enum Line {
+    Code(Instruction),
+    Word(u16),
+    Byte(u8)
+}
+
+/// Split `bytes` into instructions, and data for anything that could not be decoded
+fn listing_lines(bytes: &[u8], origin: u16) -> Vec<(u16, Line)> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    for (addr, instr) in disassemble(bytes, origin) {
+        let instr_offset = addr.wrapping_sub(origin) as usize;
+
+        // unused nybbles, like the N of 5xyN, would be lost when the listing is assembled
+        let encoded = instr.encode();
+        if bytes.get(instr_offset..(instr_offset + encoded.len())) != Some(&encoded[..]) {
+            continue;
+        }
+
+        // anything skipped by the disassembler is data
+        while offset < instr_offset {
+            lines.push((origin.wrapping_add(offset as u16), Line::Word(word(bytes, offset))));
+            offset += 2;
+        }
+
+        lines.push((addr, Line::Code(instr)));
+        offset += instr.size() as usize;
+    }
+
+    while offset + 1 < bytes.len() {
+        lines.push((origin.wrapping_add(offset as u16), Line::Word(word(bytes, offset))));
+        offset += 2;
+    }
+
+    // a trailing odd byte
+    if offset < bytes.len() {
+        lines.push((origin.wrapping_add(offset as u16), Line::Byte(bytes[offset])));
+    }
+
+    lines
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::CLS() => write!(f, "CLS"),
+            Instruction::RET() => write!(f, "RET"),
+            Instruction::JP(addr) => write!(f, "JP 0x{:03X}", addr),
+            Instruction::CALL(addr) => write!(f, "CALL 0x{:03X}", addr),
+            Instruction::SEVXB(x, b) => write!(f, "SE V{:X}, 0x{:02X}", x, b),
+            Instruction::SNEVXB(x, b) => write!(f, "SNE V{:X}, 0x{:02X}", x, b),
+            Instruction::SEVXY(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LDVXB(x, b) => write!(f, "LD V{:X}, 0x{:02X}", x, b),
+            Instruction::ADDVXB(x, b) => write!(f, "ADD V{:X}, 0x{:02X}", x, b),
+            Instruction::LDVXY(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::ORVXY(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::ANDVXY(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::XORVXY(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::ADDVXY(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::SUBVXY(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::SHR(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SUBNVXY(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::SHL(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SNEVXY(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LDI(addr) => write!(f, "LD I, 0x{:03X}", addr),
+            Instruction::JR(addr) => write!(f, "JP V0, 0x{:03X}", addr),
+            Instruction::RND(x, b) => write!(f, "RND V{:X}, 0x{:02X}", x, b),
+            Instruction::DRAW(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SKP(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SKNP(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LDVXDT(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LDVXK(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::LDDTVX(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::LDSTVX(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::ADDIVX(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LDFVX(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LDB(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::LDIVX(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LDVXI(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::SCD(n) => write!(f, "SCD {}", n),
+            Instruction::SCR() => write!(f, "SCR"),
+            Instruction::SCL() => write!(f, "SCL"),
+            Instruction::EXIT() => write!(f, "EXIT"),
+            Instruction::LOW() => write!(f, "LOW"),
+            Instruction::HIGH() => write!(f, "HIGH"),
+            Instruction::LDHFVX(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::LDRVX(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LDVXR(x) => write!(f, "LD V{:X}, R", x),
+            Instruction::SCU(n) => write!(f, "SCU {}", n),
+            Instruction::SAVE(x, y) => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LOAD(x, y) => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LDILONG(addr) => write!(f, "LD I, LONG 0x{:04X}", addr),
+            Instruction::PLANE(n) => write!(f, "PLANE {}", n),
+            Instruction::AUDIO() => write!(f, "AUDIO"),
+            Instruction::PITCH(x) => write!(f, "LD PITCH, V{:X}", x)
+        }
+    }
+}
+
+/// Address an instruction jumps or calls to
+fn jump_target(instr: Instruction) -> Option<u16> {
+    match instr {
+        Instruction::JP(addr) | Instruction::CALL(addr) | Instruction::JR(addr) => Some(addr),
+        _ => None
+    }
+}
+
+fn label(addr: u16) -> String {
+    format!("L{:03X}", addr)
+}
+
+/// Big endian word at `offset`, zero past the end of `bytes`
+fn word(bytes: &[u8], offset: usize) -> u16 {
+    let msb = bytes.get(offset).cloned().unwrap_or(0) as u16;
+    let lsb = bytes.get(offset + 1).cloned().unwrap_or(0) as u16;
+
+    (msb << 8) | lsb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word() {
+        assert_eq!(word(&[0x12, 0x34, 0x56], 0), 0x1234);
+        assert_eq!(word(&[0x12, 0x34, 0x56], 2), 0x5600);
+        assert_eq!(word(&[0x12, 0x34, 0x56], 4), 0x0000);
+    }
+}
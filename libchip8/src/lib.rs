@@ -1,22 +1,92 @@
 
 extern crate rand;
+extern crate sha1;
+
+pub mod debugger;
+mod asm;
+mod disasm;
+mod movie;
+mod quirks;
+mod random;
+mod rewind;
+mod state;
+mod trace;
+
+pub use asm::{assemble, assemble_file, AsmError};
+pub use disasm::{disassemble, listing};
+pub use movie::{rom_hash, KeyEvent, Movie, MovieError};
+pub use quirks::Quirks;
+pub use random::{RandomSource, Xorshift};
+pub use rewind::RewindBuffer;
+pub use state::StateError;
+pub use trace::{Registers, TraceEntry, TRACE_ENTRY_SIZE};
+
+use debugger::{Access, MemoryAccess};
+use movie::Replay;
+
+use std::error::Error;
 use std::fmt;
 use std::num::Wrapping;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+/// Memory size of the original Chip8
+pub const MEMORY_SIZE: usize = 4096;
+/// Memory size of an XO-CHIP system
+pub const XO_MEMORY_SIZE: usize = 65536;
 
-const MEMORY_SIZE: usize = 4096;
 const STACK_SIZE: usize = 16;
 const NUM_REGISTERS: usize = 16;
 const NUM_KEYS: usize = 16;
-const DISPLAY_WIDTH: usize = 64;
-const DISPLAY_HEIGHT: usize = 32;
-const FRAMEBUFFER_SIZE: usize = 3 * DISPLAY_WIDTH * DISPLAY_HEIGHT;
-
-const PROGRAM_START_ADDRESS: u16 = 0x200;
+const NUM_RPL_FLAGS: usize = 8;
+/// XO-CHIP keeps all sixteen registers in flags
+const XO_NUM_RPL_FLAGS: usize = 16;
+const LORES_WIDTH: usize = 64;
+const LORES_HEIGHT: usize = 32;
+const HIRES_WIDTH: usize = 128;
+const HIRES_HEIGHT: usize = 64;
+const NUM_PIXELS: usize = HIRES_WIDTH * HIRES_HEIGHT;
+const FRAMEBUFFER_SIZE: usize = 3 * NUM_PIXELS;
+const NUM_PLANES: usize = 2;
+const AUDIO_PATTERN_SIZE: usize = 16;
+/// Frames of cycles `update` will run to catch up after a stall
+const MAX_CATCH_UP_FRAMES: u32 = 60;
+
+/// Colours of the four logical pixel values produced by the two XO-CHIP planes
+const PALETTE: [(u8, u8, u8); 4] = [
+    (0, 0, 0),
+    (255, 255, 255),
+    (170, 170, 170),
+    (85, 85, 85)
+];
+
+/// Sprites of the hex digits 0-F, 4 pixels wide and 5 rows high
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+    0x20, 0x60, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0,
+    0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0x90, 0x90, 0xF0, 0x10, 0x10,
+    0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0,
+    0xF0, 0x10, 0x20, 0x40, 0x40,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0,
+    0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90,
+    0xE0, 0x90, 0xE0, 0x90, 0xE0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0,
+    0xE0, 0x90, 0x90, 0x90, 0xE0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0x80
+];
+
+const FONT_ADDRESS: u16 = 0x000;
+const BIG_FONT_ADDRESS: u16 = 0x050;
+/// Address programs are loaded at
+pub const PROGRAM_START_ADDRESS: u16 = 0x200;
 
 /// Representation of Chip8 Virtual Machine
 pub struct Chip8<'a> {
-    memory: [u8; MEMORY_SIZE],     // 4096 bytes of memory
+    memory: Vec<u8>,               // 4096 bytes of memory, 64 KiB for XO-CHIP
     stack:  [u16; STACK_SIZE],     // 16 bytes of stack
     pc:     u16,                   // program counter
     sp:     u8,                    // stack pointer
@@ -26,19 +96,52 @@ pub struct Chip8<'a> {
     st:     u8,                    // sound timer
 
     keys: [bool; NUM_KEYS],              // key values
-    key_wait: Option<Box<FnMut() -> u8 + 'a>>, // function that waits for key press and returns value
+    key_wait: KeyWait,                   // progress of an Fx0A key wait
+    display_wait: bool,                  // a sprite was drawn, waiting for the next frame
 
-    display_memory: [u8; FRAMEBUFFER_SIZE], // display memory
+    pixels: [u8; NUM_PIXELS],            // logical pixel values, one bit per plane
+    display_memory: [u8; FRAMEBUFFER_SIZE], // display memory, sized for the high resolution mode
     on_display_update: Option<Box<FnMut() + 'a>>,
+    hires: bool,                         // SCHIP 128x64 display mode
+    rpl: [u8; XO_NUM_RPL_FLAGS],         // RPL user flags, the first eight unless the memory is XO-CHIP's
+    halted: bool,                        // set by the SCHIP EXIT instruction
+    planes: u8,                          // XO-CHIP planes selected for drawing
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE], // XO-CHIP 1-bit audio pattern
+    pitch: u8,                           // XO-CHIP audio pattern playback pitch
+
+    quirks: Quirks,
+    rng: Box<RandomSource + 'a>,         // source of RND values
+    seed: u64,                           // seed of the default random source
+
+    recording: Option<Movie>,            // key changes recorded since recording started
+    replay: Option<Replay>,              // key changes being replayed
+
+    memory_accesses: Option<Vec<MemoryAccess>>, // data accesses of the last step, when tracking
+    tracer: Option<Box<FnMut(&TraceEntry) + 'a>>, // called with every executed instruction
+
+    instruction_rate: f64,               // seconds per instruction when run by `update`
+    instructions_per_frame: u32,         // cycles in each 60Hz frame
+    cycles: u64,                         // cycles run
+    frames: u64,                         // frames completed
+    frame_cycle: u32,                    // cycles run in the current frame
+    last_update: Instant,
+    pending_cycles: f64                  // fraction of a cycle due but not run by `update`
+}
 
-    instruction_rate: f64,
-    last_step: Instant,
-    time: Instant
+/// Progress of an Fx0A key wait. The machine waits for a key to be pressed and released, like the
+/// COSMAC VIP
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyWait {
+    Idle,
+    /// Waiting for a key press, the key is stored in the register
+    Press(usize),
+    /// Waiting for the pressed key to be released
+    Release(usize, u8)
 }
 
 /// Chip8 instructions
-#[derive(Debug)]
-enum Instruction {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
 //    SYS(),
     CLS(),
     RET(),
@@ -55,9 +158,9 @@ enum Instruction {
     XORVXY(usize, usize),
     ADDVXY(usize, usize),
     SUBVXY(usize, usize),
-    SHR(usize),
+    SHR(usize, usize),
     SUBNVXY(usize, usize),
-    SHL(usize),
+    SHL(usize, usize),
     SNEVXY(usize, usize),
     LDI(u16),
     JR(u16),
@@ -73,89 +176,50 @@ enum Instruction {
     LDFVX(usize),
     LDB(usize),
     LDIVX(usize),
-    LDVXI(usize)
+    LDVXI(usize),
+    // SCHIP 1.1
+    SCD(u8),
+    SCR(),
+    SCL(),
+    EXIT(),
+    LOW(),
+    HIGH(),
+    LDHFVX(usize),
+    LDRVX(usize),
+    LDVXR(usize),
+    // XO-CHIP
+    SCU(u8),
+    SAVE(usize, usize),
+    LOAD(usize, usize),
+    LDILONG(u16),
+    PLANE(u8),
+    AUDIO(),
+    PITCH(usize)
 }
 
-pub struct DecodeError {
-    opcode: u16
-}
-
-impl<'a> Chip8<'a> {
-    pub fn new(rate: f64) -> Self {
-        let mut vm = Chip8 {
-            memory: [0; MEMORY_SIZE],
-            stack:  [0; STACK_SIZE],
-            pc:     PROGRAM_START_ADDRESS,
-            sp:     0x0,
-            i:      0x0,
-            v:      [0; NUM_REGISTERS],
-            dt:     0,
-            st:     0,
-
-            keys:   [false; NUM_KEYS],
-            key_wait: Some(Box::new(||{0})),
-
-            display_memory: [0; FRAMEBUFFER_SIZE],
-            on_display_update: None,
-
-            instruction_rate: rate,
-            last_step: Instant::now(),
-            time: Instant::now()
-        };
-
-        vm.load_font();
-
-        vm
-    }
-
-    /// Update the virtual machine.
-    /// Must be run continously
-    pub fn update(&mut self) -> Result<(), DecodeError> {
-        self.update_timers();
-
-        let (elapsed, now) = get_elapsed_time(&self.last_step);
-
-        if elapsed > self.instruction_rate {
-            self.step()?;
-            self.last_step = now;
+impl Instruction {
+    /// Size of the instruction in bytes
+    pub fn size(&self) -> u16 {
+        match *self {
+            Instruction::LDILONG(_) => 4,
+            _ => 2
         }
-
-        Ok(())
     }
 
-    /// Run a single step of the VM
-    pub fn step(&mut self) -> Result<(), DecodeError> {
-        let opcode = self.fetch();
-
-        match self.decode(opcode) {
-            Ok(instr) => self.execute(instr),
-            Err(e) => return Err(e)
-        }
-
-        Ok(())
-    }
-
-    fn fetch(&mut self) -> u16 {
-        // fetch most significant byte and least significant byte from memory
-        let msb = self.memory[(self.pc) as usize];
-        let lsb = self.memory[(self.pc + 1) as usize];
-
-        // advance the program counter.
-        self.pc += 2;
-
-        let mut opcode: u16 = 0;
-        opcode |= (msb as u16) << 8;
-        opcode |= lsb as u16;
-
-        opcode
-    }
-
-    fn decode(&self, opcode: u16) -> Result<Instruction, DecodeError> {
+    /// Decode an opcode. `operand` is the word following the opcode, used by the XO-CHIP long load
+    pub fn decode(opcode: u16, operand: u16) -> Result<Instruction, DecodeError> {
         match opcode & 0xF000 {
             0x0000 => {
                 match opcode {
                     0x00E0 => return Ok(Instruction::CLS()),
                     0x00EE => return Ok(Instruction::RET()),
+                    0x00FB => return Ok(Instruction::SCR()),
+                    0x00FC => return Ok(Instruction::SCL()),
+                    0x00FD => return Ok(Instruction::EXIT()),
+                    0x00FE => return Ok(Instruction::LOW()),
+                    0x00FF => return Ok(Instruction::HIGH()),
+                    _ if opcode & 0xFFF0 == 0x00C0 => return Ok(Instruction::SCD(nybble(opcode, 0))),
+                    _ if opcode & 0xFFF0 == 0x00D0 => return Ok(Instruction::SCU(nybble(opcode, 0))),
                     _ => Err(DecodeError{opcode: opcode})
                 }
             },
@@ -163,7 +227,13 @@ impl<'a> Chip8<'a> {
             0x2000 => return Ok(Instruction::CALL(opcode & 0x0FFF)),
             0x3000 => return Ok(Instruction::SEVXB(nybble(opcode, 2) as usize, (opcode & 0x00FF) as u8)),
             0x4000 => return Ok(Instruction::SNEVXB(nybble(opcode, 2) as usize, (opcode & 0x00FF) as u8)),
-            0x5000 => return Ok(Instruction::SEVXY(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
+            0x5000 => {
+                match opcode & 0x000F {
+                    0x0002 => return Ok(Instruction::SAVE(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
+                    0x0003 => return Ok(Instruction::LOAD(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
+                    _ => return Ok(Instruction::SEVXY(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize))
+                }
+            },
             0x6000 => return Ok(Instruction::LDVXB(nybble(opcode, 2) as usize, (opcode & 0x00FF) as u8)),
             0x7000 => return Ok(Instruction::ADDVXB(nybble(opcode, 2) as usize, (opcode & 0x00FF) as u8)),
             0x8000 => {
@@ -174,9 +244,9 @@ impl<'a> Chip8<'a> {
                     0x0003 => return Ok(Instruction::XORVXY(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
                     0x0004 => return Ok(Instruction::ADDVXY(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
                     0x0005 => return Ok(Instruction::SUBVXY(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
-                    0x0006 => return Ok(Instruction::SHR(nybble(opcode, 2) as usize)),
+                    0x0006 => return Ok(Instruction::SHR(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
                     0x0007 => return Ok(Instruction::SUBNVXY(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
-                    0x000E => return Ok(Instruction::SHL(nybble(opcode, 2) as usize)),
+                    0x000E => return Ok(Instruction::SHL(nybble(opcode, 2) as usize, nybble(opcode, 1) as usize)),
                     _ => Err(DecodeError{opcode: opcode})
                 }
             },
@@ -194,15 +264,22 @@ impl<'a> Chip8<'a> {
             },
             0xF000 => {
                 match opcode & 0x00FF {
+                    0x0000 if opcode == 0xF000 => return Ok(Instruction::LDILONG(operand)),
+                    0x0001 if nybble(opcode, 2) <= 0x3 => return Ok(Instruction::PLANE(nybble(opcode, 2))),
+                    0x0002 if opcode == 0xF002 => return Ok(Instruction::AUDIO()),
                     0x0007 => return Ok(Instruction::LDVXDT(nybble(opcode, 2) as usize)),
                     0x000A => return Ok(Instruction::LDVXK(nybble(opcode, 2) as usize)),
                     0x0015 => return Ok(Instruction::LDDTVX(nybble(opcode, 2) as usize)),
                     0x0018 => return Ok(Instruction::LDSTVX(nybble(opcode, 2) as usize)),
                     0x001E => return Ok(Instruction::ADDIVX(nybble(opcode, 2) as usize)),
                     0x0029 => return Ok(Instruction::LDFVX(nybble(opcode, 2) as usize)),
+                    0x0030 => return Ok(Instruction::LDHFVX(nybble(opcode, 2) as usize)),
                     0x0033 => return Ok(Instruction::LDB(nybble(opcode, 2) as usize)),
+                    0x003A => return Ok(Instruction::PITCH(nybble(opcode, 2) as usize)),
                     0x0055 => return Ok(Instruction::LDIVX(nybble(opcode, 2) as usize)),
                     0x0065 => return Ok(Instruction::LDVXI(nybble(opcode, 2) as usize)),
+                    0x0075 => return Ok(Instruction::LDRVX(nybble(opcode, 2) as usize)),
+                    0x0085 => return Ok(Instruction::LDVXR(nybble(opcode, 2) as usize)),
                     _ => Err(DecodeError{opcode: opcode})
                 }
             },
@@ -212,12 +289,376 @@ impl<'a> Chip8<'a> {
         }
     }
 
-    fn execute(&mut self, instr: Instruction) {
+    /// Encode the instruction into big endian bytes, the inverse of `decode`
+    pub fn encode(&self) -> Vec<u8> {
+        let xy = |base: u16, x: usize, y: usize| base | ((x as u16) << 8) | ((y as u16) << 4);
+        let xb = |base: u16, x: usize, b: u8| base | ((x as u16) << 8) | b as u16;
+
+        let opcode = match *self {
+            Instruction::CLS() => 0x00E0,
+            Instruction::RET() => 0x00EE,
+            Instruction::JP(addr) => 0x1000 | addr,
+            Instruction::CALL(addr) => 0x2000 | addr,
+            Instruction::SEVXB(x, b) => xb(0x3000, x, b),
+            Instruction::SNEVXB(x, b) => xb(0x4000, x, b),
+            Instruction::SEVXY(x, y) => xy(0x5000, x, y),
+            Instruction::LDVXB(x, b) => xb(0x6000, x, b),
+            Instruction::ADDVXB(x, b) => xb(0x7000, x, b),
+            Instruction::LDVXY(x, y) => xy(0x8000, x, y),
+            Instruction::ORVXY(x, y) => xy(0x8001, x, y),
+            Instruction::ANDVXY(x, y) => xy(0x8002, x, y),
+            Instruction::XORVXY(x, y) => xy(0x8003, x, y),
+            Instruction::ADDVXY(x, y) => xy(0x8004, x, y),
+            Instruction::SUBVXY(x, y) => xy(0x8005, x, y),
+            Instruction::SHR(x, y) => xy(0x8006, x, y),
+            Instruction::SUBNVXY(x, y) => xy(0x8007, x, y),
+            Instruction::SHL(x, y) => xy(0x800E, x, y),
+            Instruction::SNEVXY(x, y) => xy(0x9000, x, y),
+            Instruction::LDI(addr) => 0xA000 | addr,
+            Instruction::JR(addr) => 0xB000 | addr,
+            Instruction::RND(x, b) => xb(0xC000, x, b),
+            Instruction::DRAW(x, y, n) => xy(0xD000, x, y) | n as u16,
+            Instruction::SKP(x) => xb(0xE09E, x, 0),
+            Instruction::SKNP(x) => xb(0xE0A1, x, 0),
+            Instruction::LDVXDT(x) => xb(0xF007, x, 0),
+            Instruction::LDVXK(x) => xb(0xF00A, x, 0),
+            Instruction::LDDTVX(x) => xb(0xF015, x, 0),
+            Instruction::LDSTVX(x) => xb(0xF018, x, 0),
+            Instruction::ADDIVX(x) => xb(0xF01E, x, 0),
+            Instruction::LDFVX(x) => xb(0xF029, x, 0),
+            Instruction::LDB(x) => xb(0xF033, x, 0),
+            Instruction::LDIVX(x) => xb(0xF055, x, 0),
+            Instruction::LDVXI(x) => xb(0xF065, x, 0),
+            Instruction::SCD(n) => 0x00C0 | n as u16,
+            Instruction::SCR() => 0x00FB,
+            Instruction::SCL() => 0x00FC,
+            Instruction::EXIT() => 0x00FD,
+            Instruction::LOW() => 0x00FE,
+            Instruction::HIGH() => 0x00FF,
+            Instruction::LDHFVX(x) => xb(0xF030, x, 0),
+            Instruction::LDRVX(x) => xb(0xF075, x, 0),
+            Instruction::LDVXR(x) => xb(0xF085, x, 0),
+            Instruction::SCU(n) => 0x00D0 | n as u16,
+            Instruction::SAVE(x, y) => xy(0x5002, x, y),
+            Instruction::LOAD(x, y) => xy(0x5003, x, y),
+            Instruction::LDILONG(_) => 0xF000,
+            Instruction::PLANE(n) => 0xF001 | (n as u16) << 8,
+            Instruction::AUDIO() => 0xF002,
+            Instruction::PITCH(x) => xb(0xF03A, x, 0)
+        };
+
+        let mut bytes = vec![(opcode >> 8) as u8, opcode as u8];
+
+        if let Instruction::LDILONG(addr) = *self {
+            bytes.push((addr >> 8) as u8);
+            bytes.push(addr as u8);
+        }
+
+        bytes
+    }
+}
+
+/// An opcode that is not a valid instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeError {
+    pub opcode: u16
+}
+
+/// A fault that stopped the virtual machine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chip8Error {
+    /// Address of the faulting instruction
+    pub pc: u16,
+    /// The faulting instruction, 0 if it could not be fetched
+    pub opcode: u16,
+    pub kind: ErrorKind
+}
+
+/// Kinds of faults
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    /// The opcode is not a valid instruction
+    InvalidOpcode,
+    /// CALL with every stack entry in use
+    StackOverflow,
+    /// RET with an empty stack
+    StackUnderflow,
+    /// Memory was accessed past its end, at this address
+    AddressOutOfRange(usize),
+    /// SKP or SKNP with a register holding a value that is not a key
+    InvalidKey(u8),
+    /// The rom does not fit in memory after the program start address
+    RomTooLarge { size: usize, max: usize }
+}
+
+impl<'a> Chip8<'a> {
+    pub fn new(rate: f64) -> Self {
+        Chip8::with_memory_size(rate, MEMORY_SIZE)
+    }
+
+    /// Create a virtual machine whose RND values are seeded with `seed`, so runs can be reproduced
+    pub fn with_seed(rate: f64, seed: u64) -> Self {
+        let mut vm = Chip8::new(rate);
+        vm.set_seed(seed);
+
+        vm
+    }
+
+    /// Create a virtual machine with `memory_size` bytes of memory, e.g. `XO_MEMORY_SIZE`
+    pub fn with_memory_size(rate: f64, memory_size: usize) -> Self {
+        let mut vm = Chip8 {
+            memory: vec![0; memory_size],
+            stack:  [0; STACK_SIZE],
+            pc:     PROGRAM_START_ADDRESS,
+            sp:     0x0,
+            i:      0x0,
+            v:      [0; NUM_REGISTERS],
+            dt:     0,
+            st:     0,
+
+            keys:   [false; NUM_KEYS],
+            key_wait: KeyWait::Idle,
+            display_wait: false,
+
+            pixels: [0; NUM_PIXELS],
+            display_memory: [0; FRAMEBUFFER_SIZE],
+            on_display_update: None,
+            hires: false,
+            rpl: [0; XO_NUM_RPL_FLAGS],
+            halted: false,
+            planes: 0x1,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: 64,
+
+            quirks: Quirks::default(),
+            rng: Box::new(Xorshift::new(0)),
+            seed: 0,
+
+            recording: None,
+            replay: None,
+
+            memory_accesses: None,
+            tracer: None,
+
+            instruction_rate: rate,
+            instructions_per_frame: ((1.0 / 60.0) / rate).round().max(1.0) as u32,
+            cycles: 0,
+            frames: 0,
+            frame_cycle: 0,
+            last_update: Instant::now(),
+            pending_cycles: 0.0
+        };
+
+        vm.load_font();
+        vm.set_seed(rand::random());
+
+        vm
+    }
+
+    /// Update the virtual machine, running the cycles due at the instruction rate since the last update.
+    /// Must be run continously
+    pub fn update(&mut self) -> Result<(), Chip8Error> {
+        let cycles = self.cycles_due();
+        self.run_for(cycles)
+    }
+
+    /// Run `cycles` instructions. The timers tick at the end of every frame
+    pub fn run_for(&mut self, cycles: u32) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Forget the time since the last update, so a paused machine does not catch up when it resumes
+    pub fn reset_clock(&mut self) {
+        self.last_update = Instant::now();
+        self.pending_cycles = 0.0;
+    }
+
+    /// Run the rest of the current frame
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        let cycles = self.frame_cycles_left();
+        self.run_for(cycles)
+    }
+
+    /// Cycles until the end of the current frame
+    fn frame_cycles_left(&self) -> u32 {
+        self.instructions_per_frame - self.frame_cycle
+    }
+
+    /// Number of cycles due at the instruction rate since the last call
+    fn cycles_due(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = duration_secs(now.duration_since(self.last_update));
+        self.last_update = now;
+
+        // long stalls are not caught up, that would freeze the machine while it runs them
+        let max_cycles = MAX_CATCH_UP_FRAMES.saturating_mul(self.instructions_per_frame) as f64;
+        self.pending_cycles = (self.pending_cycles + elapsed / self.instruction_rate).min(max_cycles);
+
+        let due = self.pending_cycles.floor();
+        self.pending_cycles -= due;
+
+        due as u32
+    }
+
+    /// Run a single step of the VM, one cycle of the clock. A halted machine, or one waiting for a
+    /// key or the next frame, only advances the clock. An instruction that faults changes nothing,
+    /// the program counter stays on it and the clock does not advance
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        self.apply_replay();
+
+        if let Some(ref mut accesses) = self.memory_accesses {
+            accesses.clear();
+        }
+
+        if !self.halted && !self.is_waiting_for_key() && !self.display_wait {
+            let pc = self.pc;
+            let mut opcode = 0;
+            let mut operand = 0;
+            let before = self.get_registers();
+
+            let result = self.fetch()
+                .and_then(|fetched| {
+                    opcode = fetched;
+
+                    // the XO-CHIP long load takes its address from the word after the opcode
+                    if opcode == 0xF000 {
+                        self.check_address(self.pc as usize + 1)?;
+                        operand = self.read_word(self.pc);
+                    }
+
+                    Instruction::decode(opcode, operand).map_err(|_| ErrorKind::InvalidOpcode)
+                })
+                .and_then(|instr| self.execute(instr).map(|_| instr));
+
+            match result {
+                Ok(instr) => {
+                    if self.tracer.is_some() {
+                        let entry = TraceEntry {
+                            cycle: self.cycles,
+                            pc: pc,
+                            opcode: opcode,
+                            operand: operand,
+                            instruction: instr,
+                            before: before,
+                            after: self.get_registers()
+                        };
+
+                        if let Some(ref mut tracer) = self.tracer {
+                            tracer(&entry);
+                        }
+                    }
+                },
+                Err(kind) => {
+                    self.pc = pc;
+
+                    return Err(Chip8Error {
+                        pc: pc,
+                        opcode: opcode,
+                        kind: kind
+                    });
+                }
+            }
+        }
+
+        self.advance_clock();
+
+        Ok(())
+    }
+
+    fn advance_clock(&mut self) {
+        self.cycles += 1;
+        self.frame_cycle += 1;
+
+        if self.frame_cycle >= self.instructions_per_frame {
+            self.frame_cycle = 0;
+            self.frames += 1;
+            self.display_wait = false;
+            self.tick_timers();
+        }
+    }
+
+    fn fetch(&mut self) -> Result<u16, ErrorKind> {
+        self.check_address(self.pc as usize + 1)?;
+
+        let opcode = self.read_word(self.pc);
+
+        // advance the program counter.
+        self.pc = self.pc.wrapping_add(2);
+
+        Ok(opcode)
+    }
+
+    fn check_address(&self, addr: usize) -> Result<usize, ErrorKind> {
+        if addr < self.memory.len() { Ok(addr) } else { Err(ErrorKind::AddressOutOfRange(addr)) }
+    }
+
+    /// Check `len` bytes from `start` are in memory before an instruction changes anything, the
+    /// error has the first address past the end like a byte by byte access
+    fn check_range(&self, start: usize, len: usize) -> Result<(), ErrorKind> {
+        if start + len <= self.memory.len() { Ok(()) } else { Err(ErrorKind::AddressOutOfRange(start.max(self.memory.len()))) }
+    }
+
+    /// Read a byte of data, recording the access if memory tracking is enabled
+    fn read_memory(&mut self, addr: usize) -> Result<u8, ErrorKind> {
+        self.check_address(addr)?;
+
+        if let Some(ref mut accesses) = self.memory_accesses {
+            accesses.push(MemoryAccess { addr: addr as u16, access: Access::Read });
+        }
+
+        Ok(self.memory[addr])
+    }
+
+    /// Write a byte of data, recording the access if memory tracking is enabled
+    fn write_memory(&mut self, addr: usize, value: u8) -> Result<(), ErrorKind> {
+        self.check_address(addr)?;
+
+        if let Some(ref mut accesses) = self.memory_accesses {
+            accesses.push(MemoryAccess { addr: addr as u16, access: Access::Write });
+        }
+
+        self.memory[addr] = value;
+
+        Ok(())
+    }
+
+    /// Read a big endian word, bytes past the end of memory read as zero
+    fn read_word(&self, addr: u16) -> u16 {
+        // fetch most significant byte and least significant byte from memory
+        let msb = self.memory.get(addr as usize).cloned().unwrap_or(0);
+        let lsb = self.memory.get(addr as usize + 1).cloned().unwrap_or(0);
+
+        let mut word: u16 = 0;
+        word |= (msb as u16) << 8;
+        word |= lsb as u16;
+
+        word
+    }
+
+    /// Skip the next instruction, including the operand of a XO-CHIP long load
+    fn skip(&mut self) {
+        if self.read_word(self.pc) == 0xF000 {
+            self.pc = self.pc.wrapping_add(4);
+        }
+        else {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn execute(&mut self, instr: Instruction) -> Result<(), ErrorKind> {
         match instr {
             Instruction::CLS() => {
                 self.clear_display();
             },
             Instruction::RET() => {
+                if self.sp == 0 {
+                    return Err(ErrorKind::StackUnderflow);
+                }
+
                 self.sp -= 1;
                 self.pc = self.stack[self.sp as usize];
             },
@@ -225,6 +666,10 @@ impl<'a> Chip8<'a> {
                 self.pc = addr;
             },
             Instruction::CALL(addr) => {
+                if self.sp as usize >= STACK_SIZE {
+                    return Err(ErrorKind::StackOverflow);
+                }
+
                 self.stack[self.sp as usize] = self.pc;
                 self.sp += 1;
 
@@ -232,17 +677,17 @@ impl<'a> Chip8<'a> {
             },
             Instruction::SEVXB(x, b) => {
                 if self.v[x] == b {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
             Instruction::SNEVXB(x, b) => {
                 if self.v[x] != b {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
             Instruction::SEVXY(x, y) => {
                 if self.v[x] == self.v[y] {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
             Instruction::LDVXB(x, b) => {
@@ -256,12 +701,15 @@ impl<'a> Chip8<'a> {
             },
             Instruction::ORVXY(x, y) => {
                 self.v[x] |= self.v[y];
+                self.reset_vf();
             },
             Instruction::ANDVXY(x, y) => {
                 self.v[x] &= self.v[y];
+                self.reset_vf();
             },
             Instruction::XORVXY(x, y) => {
                 self.v[x] ^= self.v[y];
+                self.reset_vf();
             },
             Instruction::ADDVXY(x, y) => {
                 let r: u16 = (self.v[x] as u16) + (self.v[y] as u16);
@@ -288,9 +736,10 @@ impl<'a> Chip8<'a> {
                 let wy = Wrapping(self.v[y]);
                 self.v[x] = (wx - wy).0;
             },
-            Instruction::SHR(x) => {
-                self.v[0xF] = self.v[x] & 0x01;
-                self.v[x] >>= 1;
+            Instruction::SHR(x, y) => {
+                let value = self.shift_source(x, y);
+                self.v[x] = value >> 1;
+                self.v[0xF] = value & 0x01;
             },
             Instruction::SUBNVXY(x, y) => {
                 if self.v[y] > self.v[x] {
@@ -302,51 +751,46 @@ impl<'a> Chip8<'a> {
 
                 self.v[x] = (Wrapping(self.v[y]) - Wrapping(self.v[x])).0;
             },
-            Instruction::SHL(x) => {
-                self.v[0xF] = (self.v[x] & 0x80) >> 7;
-                self.v[x] <<= 1;
+            Instruction::SHL(x, y) => {
+                let value = self.shift_source(x, y);
+                self.v[x] = value << 1;
+                self.v[0xF] = (value & 0x80) >> 7;
             },
             Instruction::SNEVXY(x, y) => {
                 if self.v[x] != self.v[y] {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
             Instruction::LDI(n) => {
                 self.i = n;
             },
             Instruction::JR(n) => {
-                self.pc = n + (self.v[0] as u16);
+                let x = if self.quirks.jump_vx { nybble(n, 2) as usize } else { 0 };
+                self.pc = n + (self.v[x] as u16);
             },
             Instruction::RND(x, b) => {
-                self.v[x] = b & rand::random::<u8>();
+                self.v[x] = b & self.rng.next_byte();
             },
             Instruction::DRAW(x, y, n) => {
-                self.draw(x, y, n as usize);
+                self.draw(x, y, n as usize)?;
+                self.display_wait = self.quirks.display_wait;
             },
             Instruction::SKP(x) => {
-                let k = self.v[x];
-                if self.keys[k as usize] {
-                    self.pc += 2;
+                if self.is_key_pressed(self.v[x])? {
+                    self.skip();
                 }
             },
             Instruction::SKNP(x) => {
-                let k = self.v[x];
-                if !self.keys[k as usize] {
-                    self.pc += 2;
+                if !self.is_key_pressed(self.v[x])? {
+                    self.skip();
                 }
             },
             Instruction::LDVXDT(x) => {
                 self.v[x] = self.dt;
             },
             Instruction::LDVXK(x) => {
-                match self.key_wait {
-                    Some(ref mut key_wait) => {
-                        self.v[x] = key_wait();
-                    },
-                    None => {
-                        panic!("Keypad instruction used without the key_wait being set");
-                    }
-                }
+                // the key is stored when it is released, see `set_key`
+                self.key_wait = KeyWait::Press(x);
             },
             Instruction::LDDTVX(x) => {
                 self.dt = self.v[x];
@@ -358,32 +802,133 @@ impl<'a> Chip8<'a> {
                 self.i = (Wrapping(self.i) + Wrapping(self.v[x] as u16)).0;
             },
             Instruction::LDFVX(x) => {
-                self.i = (self.v[x] as u16 * 5) as u16;
+                self.i = FONT_ADDRESS + (self.v[x] & 0x0F) as u16 * 5;
+            },
+            Instruction::LDHFVX(x) => {
+                self.i = BIG_FONT_ADDRESS + (self.v[x] & 0x0F) as u16 * 10;
             },
             Instruction::LDB(x) => {
                 let (h, t, o) = bcd(self.v[x]);
-                self.memory[self.i as usize]       = h;
-                self.memory[(self.i + 1) as usize] = t;
-                self.memory[(self.i + 2) as usize] = o;
+                let addr = self.i as usize;
+                self.check_range(addr, 3)?;
+
+                self.write_memory(addr,     h)?;
+                self.write_memory(addr + 1, t)?;
+                self.write_memory(addr + 2, o)?;
             },
             Instruction::LDIVX(x) => {
-                for i in 0..x {
-                    let addr = (self.i + i as u16) as usize;
-                    self.memory[addr] = self.v[i];
+                self.check_range(self.i as usize, x + 1)?;
+
+                for i in 0..(x + 1) {
+                    let addr = self.i as usize + i;
+                    let value = self.v[i];
+                    self.write_memory(addr, value)?;
+                }
+
+                if self.quirks.load_store_increment_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
                 }
             },
             Instruction::LDVXI(x) => {
-                for i in 0..x {
-                    let addr = (self.i + i as u16) as usize;
-                    self.v[i] = self.memory[addr];
+                self.check_range(self.i as usize, x + 1)?;
+
+                for i in 0..(x + 1) {
+                    let addr = self.i as usize + i;
+                    self.v[i] = self.read_memory(addr)?;
+                }
+
+                if self.quirks.load_store_increment_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            },
+            Instruction::SCD(n) => {
+                self.scroll_down(n as usize);
+            },
+            Instruction::SCR() => {
+                self.scroll_horizontal(4);
+            },
+            Instruction::SCL() => {
+                self.scroll_horizontal(-4);
+            },
+            Instruction::EXIT() => {
+                self.halted = true;
+            },
+            Instruction::LOW() => {
+                self.set_hires(false);
+            },
+            Instruction::HIGH() => {
+                self.set_hires(true);
+            },
+            Instruction::LDRVX(x) => {
+                if x >= self.num_rpl_flags() {
+                    return Err(ErrorKind::InvalidOpcode);
                 }
+
+                self.rpl[..(x + 1)].copy_from_slice(&self.v[..(x + 1)]);
+            },
+            Instruction::LDVXR(x) => {
+                if x >= self.num_rpl_flags() {
+                    return Err(ErrorKind::InvalidOpcode);
+                }
+
+                self.v[..(x + 1)].copy_from_slice(&self.rpl[..(x + 1)]);
+            },
+            Instruction::SCU(n) => {
+                self.scroll_up(n as usize);
+            },
+            Instruction::SAVE(x, y) => {
+                let registers = register_range(x, y);
+                self.check_range(self.i as usize, registers.len())?;
+
+                for (offset, r) in registers.into_iter().enumerate() {
+                    let addr = self.i as usize + offset;
+                    let value = self.v[r];
+                    self.write_memory(addr, value)?;
+                }
+            },
+            Instruction::LOAD(x, y) => {
+                let registers = register_range(x, y);
+                self.check_range(self.i as usize, registers.len())?;
+
+                for (offset, r) in registers.into_iter().enumerate() {
+                    let addr = self.i as usize + offset;
+                    self.v[r] = self.read_memory(addr)?;
+                }
+            },
+            Instruction::LDILONG(addr) => {
+                self.i = addr;
+                // skip over the address operand
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Instruction::PLANE(n) => {
+                self.planes = n;
+            },
+            Instruction::AUDIO() => {
+                let start = self.i as usize;
+                self.check_range(start, AUDIO_PATTERN_SIZE)?;
+
+                for i in 0..AUDIO_PATTERN_SIZE {
+                    self.audio_pattern[i] = self.read_memory(start + i)?;
+                }
+            },
+            Instruction::PITCH(x) => {
+                self.pitch = self.v[x];
             }
         }
+
+        Ok(())
     }
 
-    pub fn load_memory(&mut self, memory: Vec<u8>) {
-        if memory.len() > MEMORY_SIZE - (PROGRAM_START_ADDRESS as usize) {
-            panic!("provided memory will not fit in vm");
+    /// Load a rom at the program start address
+    pub fn load_memory(&mut self, memory: Vec<u8>) -> Result<(), Chip8Error> {
+        let max = self.memory.len() - (PROGRAM_START_ADDRESS as usize);
+
+        if memory.len() > max {
+            return Err(Chip8Error {
+                pc: 0,
+                opcode: 0,
+                kind: ErrorKind::RomTooLarge { size: memory.len(), max: max }
+            });
         }
 
         let program_start_offset = PROGRAM_START_ADDRESS as usize;
@@ -391,20 +936,113 @@ impl<'a> Chip8<'a> {
         for (i, byte) in memory.iter().enumerate() {
             self.memory[program_start_offset + i] = *byte;
         }
+
+        Ok(())
     }
 
+    /// Set the state of a key. Ignored while a movie is replaying
     pub fn key(&mut self, k: u8, val: bool) {
+        if self.is_replaying() {
+            return;
+        }
+
+        if self.keys[k as usize] != val {
+            self.record_key(k, val);
+        }
+
+        self.set_key(k, val);
+    }
+
+    fn set_key(&mut self, k: u8, val: bool) {
+        let was_pressed = self.keys[k as usize];
         self.keys[k as usize] = val;
+
+        match self.key_wait {
+            KeyWait::Press(x) if val && !was_pressed => {
+                self.key_wait = KeyWait::Release(x, k);
+            },
+            KeyWait::Release(x, key) if key == k && !val => {
+                self.v[x] = k;
+                self.key_wait = KeyWait::Idle;
+            },
+            _ => {}
+        }
     }
 
-    pub fn set_key_wait(&mut self, key_wait: Box<FnMut() -> u8 + 'a>) {
-        self.key_wait = Some(key_wait);
+    fn is_key_pressed(&self, k: u8) -> Result<bool, ErrorKind> {
+        match self.keys.get(k as usize) {
+            Some(pressed) => Ok(*pressed),
+            None => Err(ErrorKind::InvalidKey(k))
+        }
+    }
+
+    /// True while an Fx0A instruction waits for a key to be pressed and released
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait != KeyWait::Idle
+    }
+
+    /// True while a sprite drawn with the `display_wait` quirk waits for the next frame
+    pub fn is_waiting_for_display(&self) -> bool {
+        self.display_wait
+    }
+
+    /// Record a key change if recording
+    fn record_key(&mut self, key: u8, pressed: bool) {
+        let frame = self.frames;
+        let cycle = self.cycles;
+
+        if let Some(ref mut movie) = self.recording {
+            movie.events.push(KeyEvent {
+                frame: frame,
+                cycle: cycle,
+                key: key,
+                pressed: pressed
+            });
+        }
+    }
+
+    /// Apply the replayed key changes due before the next cycle
+    fn apply_replay(&mut self) {
+        let mut changes = Vec::new();
+
+        if let Some(ref mut replay) = self.replay {
+            while let Some(event) = replay.events.get(replay.next) {
+                if event.cycle > self.cycles {
+                    break;
+                }
+
+                changes.push((event.key, event.pressed));
+                replay.next += 1;
+            }
+        }
+
+        for (key, pressed) in changes {
+            self.set_key(key, pressed);
+        }
+    }
+
+    /// Call `tracer` with every executed instruction
+    pub fn set_tracer(&mut self, tracer: Box<FnMut(&TraceEntry) + 'a>) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn clear_tracer(&mut self) {
+        self.tracer = None;
     }
 
     pub fn set_on_display_update(&mut self, on_display_update: Box<FnMut() + 'a>) {
         self.on_display_update = Some(on_display_update);
     }
 
+    /// Select how ambiguous instructions behave
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn get_quirks(&self) -> Quirks {
+        self.quirks
+    }
+
     pub fn get_register(&self, x: usize) -> u8 {
         self.v[x]
     }
@@ -425,6 +1063,14 @@ impl<'a> Chip8<'a> {
         self.i
     }
 
+    /// The V registers and I
+    pub fn get_registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i
+        }
+    }
+
     pub fn get_dt(&self) -> u8 {
         self.dt
     }
@@ -433,8 +1079,138 @@ impl<'a> Chip8<'a> {
         self.st
     }
 
+    /// The buzzer sounds while the sound timer is non-zero
+    pub fn is_sound_active(&self) -> bool {
+        self.st > 0
+    }
+
+    /// Count the delay and sound timers down by one 60Hz tick. The clock does this at the end of every frame
+    pub fn tick_timers(&mut self) {
+        self.update_delay_timer();
+        self.update_sound_timer();
+    }
+
+    /// Reseed the default random source
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Box::new(Xorshift::new(seed));
+        self.seed = seed;
+    }
+
+    /// Seed of the default random source, recorded in movies
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Replace the source of RND values
+    pub fn set_random_source(&mut self, rng: Box<RandomSource + 'a>) {
+        self.rng = rng;
+    }
+
+    /// Set the cycles in each 60Hz frame. The instruction rate used by `update` follows it
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+        self.instruction_rate = 1.0 / (60.0 * self.instructions_per_frame as f64);
+        self.frame_cycle = self.frame_cycle.min(self.instructions_per_frame - 1);
+    }
+
+    pub fn get_instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    /// Cycles run since the machine was created
+    pub fn get_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Frames completed since the machine was created
+    pub fn get_frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Framebuffer for the active resolution as RGB24, `get_display_width()` pixels per row
     pub fn get_display_memory(&self) -> &[u8] {
-        &self.display_memory
+        let size = 3 * self.get_display_width() * self.get_display_height();
+        &self.display_memory[..size]
+    }
+
+    pub fn get_display_width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn get_display_height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn get_rpl_flag(&self, x: usize) -> u8 {
+        self.rpl[x]
+    }
+
+    /// All RPL user flags, for frontends to keep them after the program exits. SCHIP has 8 flags,
+    /// XO-CHIP 16
+    pub fn get_rpl_flags(&self) -> &[u8] {
+        &self.rpl[..self.num_rpl_flags()]
+    }
+
+    /// Restore RPL user flags kept from an earlier run. Flags past the end of `flags` are cleared
+    pub fn set_rpl_flags(&mut self, flags: &[u8]) {
+        let count = self.num_rpl_flags();
+
+        for (i, flag) in self.rpl[..count].iter_mut().enumerate() {
+            *flag = flags.get(i).cloned().unwrap_or(0);
+        }
+    }
+
+    /// Number of RPL user flags, XO-CHIP programs get one for every register
+    fn num_rpl_flags(&self) -> usize {
+        if self.memory.len() > MEMORY_SIZE { XO_NUM_RPL_FLAGS } else { NUM_RPL_FLAGS }
+    }
+
+    pub fn get_memory_size(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn get_memory(&self, addr: usize) -> u8 {
+        self.memory[addr]
+    }
+
+    /// Record the data reads and writes made by each step, see `get_memory_accesses`
+    pub fn set_memory_tracking(&mut self, enabled: bool) {
+        self.memory_accesses = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// Data reads and writes made by the last step. Instruction fetches are not included
+    pub fn get_memory_accesses(&self) -> &[MemoryAccess] {
+        match self.memory_accesses {
+            Some(ref accesses) => accesses,
+            None => &[]
+        }
+    }
+
+    /// Bit mask of the XO-CHIP planes selected for drawing
+    pub fn get_planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// The 128 bit XO-CHIP audio pattern, played most significant bit first
+    pub fn get_audio_pattern(&self) -> &[u8] {
+        &self.audio_pattern
+    }
+
+    pub fn get_pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Playback rate of the audio pattern in bits per second
+    pub fn get_audio_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
     }
 
     pub fn get_pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
@@ -447,80 +1223,218 @@ impl<'a> Chip8<'a> {
         (r, g, b)
     }
 
-    fn draw(&mut self, x: usize, y: usize, n: usize) {
-        let start_address = self.i as usize;
+    /// Logical value of a pixel, bit `n` is set if the pixel is on in plane `n + 1`. Frontends map
+    /// the values 0-3 to colours, the framebuffer uses a grey scale
+    pub fn get_pixel_value(&self, x: usize, y: usize) -> u8 {
+        self.pixels[(y * self.get_display_width()) + x]
+    }
+
+    /// Logical values of the displayed pixels, row by row
+    pub fn get_pixel_values(&self) -> &[u8] {
+        let size = self.get_display_width() * self.get_display_height();
+        &self.pixels[..size]
+    }
 
-        let x = self.v[x] as usize;
-        let y = self.v[y] as usize;
+    fn draw(&mut self, x: usize, y: usize, n: usize) -> Result<(), ErrorKind> {
+        let width = self.get_display_width();
+        let height = self.get_display_height();
 
-        // for bytes in sprite
-        for i in 0..n {
-            let byte = self.memory[start_address + i];
+        // the starting position always wraps, the sprite itself is clipped or wrapped
+        let x = self.v[x] as usize % width;
+        let y = self.v[y] as usize % height;
+        let clip = self.quirks.clip_sprites;
 
-            let pixel_y = (y + i) % DISPLAY_HEIGHT;
-            // pixels on/off state is encoded in the bits
-            for (c, bit) in (0..8).rev().enumerate() {
-                let pixel_x = (x + c) % DISPLAY_WIDTH;
-                // state of current pixel
-                let state = byte & (1 << bit) != 0;
-                let prev_state = self.is_pixel_set(pixel_x, pixel_y);
+        // a sprite height of zero draws a 16x16 sprite using two bytes per row
+        let (rows, bytes_per_row) = if n == 0 { (16, 2) } else { (n, 1) };
 
-                let current_state = state ^ prev_state;
-                self.v[0xF] = current_state as u8;
+        let mut collision = false;
+        let mut start_address = self.i as usize;
 
-                self.set_pixel(pixel_x, pixel_y, current_state);
+        // check the rows each plane reads are in memory before drawing any of them
+        let visible_rows = if clip { rows.min(height - y) } else { rows };
+        let mut plane_address = start_address;
+
+        for plane in 0..NUM_PLANES {
+            if self.planes & (1 << plane) != 0 && visible_rows > 0 {
+                self.check_range(plane_address, visible_rows * bytes_per_row)?;
+                plane_address += rows * bytes_per_row;
             }
         }
 
-        match self.on_display_update {
-            Some(ref mut on_display_update) => on_display_update(),
-            None => {}
+        // each selected plane is drawn with its own sprite data, stored one after the other
+        for plane in 0..NUM_PLANES {
+            let mask = 1u8 << plane;
+
+            if self.planes & mask == 0 {
+                continue;
+            }
+
+            // for rows in sprite
+            for row in 0..rows {
+                if clip && y + row >= height {
+                    break;
+                }
+
+                let pixel_y = (y + row) % height;
+
+                for b in 0..bytes_per_row {
+                    let byte = self.read_memory(start_address + (row * bytes_per_row) + b)?;
+
+                    // pixels on/off state is encoded in the bits
+                    for (c, bit) in (0..8).rev().enumerate() {
+                        // only set bits toggle a pixel
+                        if byte & (1 << bit) == 0 {
+                            continue;
+                        }
+
+                        if clip && x + (b * 8) + c >= width {
+                            continue;
+                        }
+
+                        let pixel_x = (x + (b * 8) + c) % width;
+                        let value = self.get_pixel_value(pixel_x, pixel_y);
+
+                        collision |= value & mask != 0;
+
+                        self.set_pixel_value(pixel_x, pixel_y, value ^ mask);
+                    }
+                }
+            }
+
+            start_address += rows * bytes_per_row;
+        }
+
+        self.v[0xF] = collision as u8;
+
+        self.notify_display_update();
+
+        Ok(())
+    }
+
+    /// Source register of a shift, VY if the `shift_vy` quirk is set
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.quirks.shift_vy { self.v[y] } else { self.v[x] }
+    }
+
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
         }
     }
 
     fn clear_display(&mut self) {
-        for x in 0..DISPLAY_WIDTH {
-            for y in 0..DISPLAY_HEIGHT {
-                self.set_pixel(x, y, false);
+        let planes = self.planes;
+        self.clear_planes(planes);
+    }
+
+    fn clear_planes(&mut self, planes: u8) {
+        for y in 0..self.get_display_height() {
+            for x in 0..self.get_display_width() {
+                let value = self.get_pixel_value(x, y);
+                self.set_pixel_value(x, y, value & !planes);
+            }
+        }
+
+        self.notify_display_update();
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let width = self.get_display_width();
+        let height = self.get_display_height();
+
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let src = if y >= n { self.get_pixel_value(x, y - n) } else { 0 };
+                self.scroll_pixel(x, y, src);
             }
         }
 
+        self.notify_display_update();
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let width = self.get_display_width();
+        let height = self.get_display_height();
+
+        for y in 0..height {
+            for x in 0..width {
+                let src = if y + n < height { self.get_pixel_value(x, y + n) } else { 0 };
+                self.scroll_pixel(x, y, src);
+            }
+        }
+
+        self.notify_display_update();
+    }
+
+    /// Scroll the display `n` pixels right, or left for a negative `n`
+    fn scroll_horizontal(&mut self, n: isize) {
+        let width = self.get_display_width() as isize;
+        let height = self.get_display_height();
+
+        for y in 0..height {
+            let row: Vec<u8> = (0..width).map(|x| self.get_pixel_value(x as usize, y)).collect();
+
+            for x in 0..width {
+                let src = x - n;
+                let src = if src >= 0 && src < width { row[src as usize] } else { 0 };
+                self.scroll_pixel(x as usize, y, src);
+            }
+        }
+
+        self.notify_display_update();
+    }
+
+    /// Move the selected planes of the `src` pixel value into the pixel at `x`, `y`
+    fn scroll_pixel(&mut self, x: usize, y: usize, src: u8) {
+        let value = self.get_pixel_value(x, y);
+        let planes = self.planes;
+        self.set_pixel_value(x, y, (value & !planes) | (src & planes));
+    }
+
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        // switching resolution leaves the display in an undefined state, clear it
+        self.clear_planes(0x3);
+    }
+
+    fn notify_display_update(&mut self) {
         match self.on_display_update {
             Some(ref mut on_display_update) => on_display_update(),
             None => {}
         }
     }
 
-    fn set_pixel(&mut self, x: usize, y: usize, is_on: bool) {
-        let index = self.pixel_index(x, y);
+    fn set_pixel_value(&mut self, x: usize, y: usize, value: u8) {
+        let pixel = (y * self.get_display_width()) + x;
+        self.pixels[pixel] = value;
 
-        let value = if is_on { 255u8 } else { 0u8 };
+        self.write_framebuffer(x, y, value);
+    }
 
-        // write value into framebuffer
-        self.display_memory[index + 0] = value;
-        self.display_memory[index + 1] = value;
-        self.display_memory[index + 2] = value;
+    /// Regenerate the framebuffer from the logical pixel values
+    fn refresh_display(&mut self) {
+        for y in 0..self.get_display_height() {
+            for x in 0..self.get_display_width() {
+                let value = self.get_pixel_value(x, y);
+                self.write_framebuffer(x, y, value);
+            }
+        }
+
+        self.notify_display_update();
     }
 
-    fn is_pixel_set(&self, x: usize, y: usize) -> bool {
+    fn write_framebuffer(&mut self, x: usize, y: usize, value: u8) {
         let index = self.pixel_index(x, y);
+        let (r, g, b) = PALETTE[value as usize];
 
-        // check if the specified pixel is on
-        self.display_memory[index] == 255
+        // write value into framebuffer
+        self.display_memory[index + 0] = r;
+        self.display_memory[index + 1] = g;
+        self.display_memory[index + 2] = b;
     }
 
     fn pixel_index(&self, x: usize, y: usize) -> usize {
-        (y * (DISPLAY_WIDTH * 3)) + (x * 3)
-    }
-
-    fn update_timers(&mut self) {
-        // Timers are updated at 60Hz
-        let (elapsed, now) = get_elapsed_time(&self.time);
-
-        if elapsed >= (1.0/60.0) {
-            self.update_delay_timer();
-            self.time = now;
-        }
+        (y * (self.get_display_width() * 3)) + (x * 3)
     }
 
     fn update_delay_timer(&mut self) {
@@ -529,41 +1443,77 @@ impl<'a> Chip8<'a> {
         }
     }
 
+    fn update_sound_timer(&mut self) {
+        if self.st > 0 {
+            self.st -= 1;
+        }
+    }
+
     fn load_font(&mut self) {
-        let fonts: [u8; 80] = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0,
-            0x20, 0x60, 0x20, 0x20, 0x70,
-            0xF0, 0x10, 0xF0, 0x80, 0xF0,
-            0xF0, 0x10, 0xF0, 0x10, 0xF0,
-            0x90, 0x90, 0xF0, 0x10, 0x10,
-            0xF0, 0x80, 0xF0, 0x10, 0xF0,
-            0xF0, 0x80, 0xF0, 0x90, 0xF0,
-            0xF0, 0x10, 0x20, 0x40, 0x40,
-            0xF0, 0x90, 0xF0, 0x90, 0xF0,
-            0xF0, 0x90, 0xF0, 0x10, 0xF0,
-            0xF0, 0x90, 0xF0, 0x90, 0x90,
-            0xE0, 0x90, 0xE0, 0x90, 0xE0,
-            0xF0, 0x80, 0x80, 0x80, 0xF0,
-            0xE0, 0x90, 0x90, 0x90, 0xE0,
-            0xF0, 0x80, 0xF0, 0x80, 0xF0,
-            0xF0, 0x80, 0xF0, 0x80, 0x80
+        let big_fonts: [u8; 160] = [
+            0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF,
+            0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+            0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC,
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0
         ];
 
-        for (i, item) in fonts.iter().enumerate() {
-            self.memory[i] = *item;
+        for (i, item) in FONT.iter().enumerate() {
+            self.memory[FONT_ADDRESS as usize + i] = *item;
+        }
+
+        for (i, item) in big_fonts.iter().enumerate() {
+            self.memory[BIG_FONT_ADDRESS as usize + i] = *item;
         }
     }
 }
 
-impl fmt::Display for Instruction {
+impl fmt::Display for DecodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "Failed to decode opcode: {:04X}", self.opcode)
     }
 }
 
-impl fmt::Debug for DecodeError {
+impl Error for DecodeError {
+    fn description(&self) -> &str {
+        "invalid opcode"
+    }
+}
+
+impl fmt::Display for Chip8Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Failed to decode opcode: {:X}", self.opcode)
+        // a rom that does not fit is not a fault of an instruction
+        if let ErrorKind::RomTooLarge { size, max } = self.kind {
+            return write!(f, "Rom is {} bytes, only {} fit in memory", size, max);
+        }
+
+        write!(f, "{:04X}: ", self.pc)?;
+
+        match self.kind {
+            ErrorKind::InvalidOpcode => write!(f, "Invalid opcode {:04X}", self.opcode),
+            ErrorKind::StackOverflow => write!(f, "Stack overflow in {:04X}", self.opcode),
+            ErrorKind::StackUnderflow => write!(f, "Stack underflow in {:04X}", self.opcode),
+            ErrorKind::AddressOutOfRange(addr) => write!(f, "Address {:X} is out of range", addr),
+            ErrorKind::InvalidKey(k) => write!(f, "Invalid key {:X} in {:04X}", k, self.opcode),
+            ErrorKind::RomTooLarge { .. } => Ok(())
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn description(&self) -> &str {
+        "virtual machine fault"
     }
 }
 
@@ -574,6 +1524,16 @@ fn nybble(value: u16, n: u8) -> u8 {
     ((value & mask) >> shift) as u8
 }
 
+/// Registers from `x` to `y` inclusive, in descending order if `x` is greater than `y`
+fn register_range(x: usize, y: usize) -> Vec<usize> {
+    if x <= y {
+        (x..(y + 1)).collect()
+    }
+    else {
+        (y..(x + 1)).rev().collect()
+    }
+}
+
 fn bcd(value: u8) -> (u8, u8, u8) {
     let mut dec = value;
     let o = dec % 10;
@@ -585,14 +1545,8 @@ fn bcd(value: u8) -> (u8, u8, u8) {
     (h, t, o)
 }
 
-fn get_elapsed_time(time: &Instant) -> (f64, Instant) {
-    // get current time
-    let now = Instant::now();
-    // get duration between the old time and now
-    let elapsed = now.duration_since(*time);
-
-    // return as a float
-    (elapsed.subsec_nanos() as f64 * 1e-9, now)
+fn duration_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
 }
 
 pub fn version() -> &'static str {
@@ -621,4 +1575,11 @@ mod tests {
         assert_eq!(nybble(value, 1), 0xA);
         assert_eq!(nybble(value, 0), 0xD);
     }
+
+    #[test]
+    fn test_register_range() {
+        assert_eq!(register_range(2, 4), vec![2, 3, 4]);
+        assert_eq!(register_range(4, 2), vec![4, 3, 2]);
+        assert_eq!(register_range(5, 5), vec![5]);
+    }
 }
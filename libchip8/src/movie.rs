@@ -0,0 +1,253 @@
+//! Input recording and replay
+//!
+//! A movie is the header needed to recreate the machine, followed by every key change and the
+//! cycle it happened at. Replaying it on a machine started from the same rom reproduces the run
+//! exactly.
+
+use super::*;
+use state::{read_u32, read_u64, write_u32, write_u64};
+
+use sha1::Sha1;
+
+use std::error::Error;
+
+const MAGIC: &'static [u8; 4] = b"QZ8M";
+const VERSION: u8 = 1;
+
+const HEADER_SIZE: usize = 4 + 1 + 20 + 8 + 4 + 4 + 1 + 4;
+const EVENT_SIZE: usize = 8 + 8 + 1 + 1;
+
+/// A change of a key's state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEvent {
+    /// Frame the change happened in
+    pub frame: u64,
+    /// Cycle the change happened before
+    pub cycle: u64,
+    pub key: u8,
+    pub pressed: bool
+}
+
+/// A recording of the input of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    /// SHA-1 of the rom the movie was recorded with
+    pub rom_hash: [u8; 20],
+    pub seed: u64,
+    pub instructions_per_frame: u32,
+    pub memory_size: usize,
+    pub quirks: Quirks,
+    pub events: Vec<KeyEvent>
+}
+
+/// Reasons a movie could not be read or replayed
+#[derive(Debug, PartialEq)]
+pub enum MovieError {
+    /// The data is not a movie
+    BadMagic,
+    /// The movie was written by an incompatible version
+    UnsupportedVersion(u8),
+    /// The data ended in the middle of the movie
+    Truncated,
+    /// A key event is invalid or out of order
+    InvalidEvent(usize),
+    /// The movie was recorded with a different rom
+    RomMismatch,
+    /// The movie was recorded on a machine with a different amount of memory
+    MemorySizeMismatch { expected: usize, found: usize }
+}
+
+/// Key events still to be replayed
+pub struct Replay {
+    pub events: Vec<KeyEvent>,
+    pub next: usize
+}
+
+/// SHA-1 hash of a rom
+pub fn rom_hash(rom: &[u8]) -> [u8; 20] {
+    Sha1::from(rom).digest().bytes()
+}
+
+impl Movie {
+    /// Serialize the movie
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&self.rom_hash);
+        write_u64(&mut data, self.seed);
+        write_u32(&mut data, self.instructions_per_frame);
+        write_u32(&mut data, self.memory_size as u32);
+        data.push(quirk_bits(&self.quirks));
+        write_u32(&mut data, self.events.len() as u32);
+
+        for event in self.events.iter() {
+            write_u64(&mut data, event.frame);
+            write_u64(&mut data, event.cycle);
+            data.push(event.key);
+            data.push(event.pressed as u8);
+        }
+
+        data
+    }
+
+    /// Read a movie created by `to_bytes`
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        if data.len() < MAGIC.len() + 1 || &data[..MAGIC.len()] != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+
+        let version = data[MAGIC.len()];
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        if data.len() < HEADER_SIZE {
+            return Err(MovieError::Truncated);
+        }
+
+        let mut rom_hash = [0; 20];
+        rom_hash.copy_from_slice(&data[5..25]);
+
+        let num_events = read_u32(&data[42..]) as usize;
+        let events_data = &data[HEADER_SIZE..];
+
+        if events_data.len() < num_events * EVENT_SIZE {
+            return Err(MovieError::Truncated);
+        }
+
+        let mut events: Vec<KeyEvent> = Vec::new();
+
+        for (i, event) in events_data.chunks(EVENT_SIZE).take(num_events).enumerate() {
+            let event = KeyEvent {
+                frame: read_u64(&event[0..]),
+                cycle: read_u64(&event[8..]),
+                key: event[16],
+                pressed: event[17] != 0
+            };
+
+            let in_order = events.last().map_or(true, |last| last.cycle <= event.cycle);
+
+            if event.key as usize >= NUM_KEYS || !in_order {
+                return Err(MovieError::InvalidEvent(i));
+            }
+
+            events.push(event);
+        }
+
+        Ok(Movie {
+            rom_hash: rom_hash,
+            seed: read_u64(&data[25..]),
+            instructions_per_frame: read_u32(&data[33..]),
+            memory_size: read_u32(&data[37..]) as usize,
+            quirks: quirks_from_bits(data[41]),
+            events: events
+        })
+    }
+
+    /// Set up a newly created machine to replay the movie. The seed, quirks and speed are applied
+    /// and `rom` must be the rom the movie was recorded with
+    pub fn replay(&self, vm: &mut Chip8, rom: &[u8]) -> Result<(), MovieError> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(MovieError::RomMismatch);
+        }
+
+        if vm.get_memory_size() != self.memory_size {
+            return Err(MovieError::MemorySizeMismatch {
+                expected: vm.get_memory_size(),
+                found: self.memory_size
+            });
+        }
+
+        vm.set_seed(self.seed);
+        vm.set_quirks(self.quirks);
+        vm.set_instructions_per_frame(self.instructions_per_frame);
+
+        vm.replay = Some(Replay {
+            events: self.events.clone(),
+            next: 0
+        });
+
+        Ok(())
+    }
+}
+
+impl<'a> Chip8<'a> {
+    /// Start recording key changes into a movie of `rom`. The machine should have just been
+    /// created, with its seed, quirks and speed set
+    pub fn start_recording(&mut self, rom: &[u8]) {
+        self.recording = Some(Movie {
+            rom_hash: rom_hash(rom),
+            seed: self.seed,
+            instructions_per_frame: self.instructions_per_frame,
+            memory_size: self.memory.len(),
+            quirks: self.quirks,
+            events: Vec::new()
+        });
+    }
+
+    /// Stop recording and return the movie
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// True while a movie is replaying. Key changes passed to `key` are ignored until it ends
+    pub fn is_replaying(&self) -> bool {
+        self.replay.as_ref().map_or(false, |replay| replay.next < replay.events.len())
+    }
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MovieError::BadMagic => write!(f, "Not a movie file"),
+            MovieError::UnsupportedVersion(v) => write!(f, "Unsupported movie version: {}", v),
+            MovieError::Truncated => write!(f, "Movie is truncated"),
+            MovieError::InvalidEvent(i) => write!(f, "Movie key event {} is invalid", i),
+            MovieError::RomMismatch => write!(f, "Movie was recorded with a different rom"),
+            MovieError::MemorySizeMismatch { expected, found } => {
+                write!(f, "Movie was recorded with {} bytes of memory, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl Error for MovieError {
+    fn description(&self) -> &str {
+        "invalid movie"
+    }
+}
+
+fn quirk_bits(quirks: &Quirks) -> u8 {
+    let flags = [quirks.shift_vy, quirks.load_store_increment_i, quirks.jump_vx, quirks.clip_sprites, quirks.vf_reset, quirks.display_wait];
+    flags.iter().enumerate().fold(0, |bits, (i, &flag)| bits | ((flag as u8) << i))
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let flag = |i: u8| bits & (1 << i) != 0;
+
+    Quirks {
+        shift_vy: flag(0),
+        load_store_increment_i: flag(1),
+        jump_vx: flag(2),
+        clip_sprites: flag(3),
+        vf_reset: flag(4),
+        display_wait: flag(5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quirk_bits() {
+        for quirks in [Quirks::default(), Quirks::cosmac_vip(), Quirks::chip48(), Quirks::schip()].iter() {
+            assert_eq!(quirks_from_bits(quirk_bits(quirks)), *quirks);
+        }
+    }
+}
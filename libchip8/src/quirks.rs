@@ -0,0 +1,107 @@
+/// Behaviours that differ between Chip8 interpreters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift VY into VX instead of shifting VX in place
+    pub shift_vy: bool,
+    /// `Fx55`/`Fx65` leave I pointing past the last register stored or loaded
+    pub load_store_increment_i: bool,
+    /// `Bnnn` jumps to `nnn + VX`, where X is the high nybble of `nnn`, instead of `nnn + V0`
+    pub jump_vx: bool,
+    /// Sprites are clipped at the edges of the display instead of wrapping around
+    pub clip_sprites: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset VF to zero
+    pub vf_reset: bool,
+    /// `Dxyn` waits for the next frame, like the VIP waiting for the display interrupt, so at most one
+    /// sprite is drawn per frame
+    pub display_wait: bool
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_vy: true,
+            load_store_increment_i: true,
+            jump_vx: false,
+            clip_sprites: true,
+            vf_reset: true,
+            display_wait: true
+        }
+    }
+
+    /// The CHIP-48 interpreter for the HP48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_vy: false,
+            load_store_increment_i: true,
+            jump_vx: true,
+            clip_sprites: true,
+            vf_reset: false,
+            display_wait: false
+        }
+    }
+
+    /// SUPER-CHIP 1.1
+    pub fn schip() -> Self {
+        Quirks {
+            shift_vy: false,
+            load_store_increment_i: false,
+            jump_vx: true,
+            clip_sprites: true,
+            vf_reset: false,
+            display_wait: false
+        }
+    }
+
+    /// XO-CHIP, as implemented by Octo
+    pub fn xochip() -> Self {
+        Quirks {
+            shift_vy: true,
+            load_store_increment_i: true,
+            jump_vx: false,
+            clip_sprites: false,
+            vf_reset: false,
+            display_wait: false
+        }
+    }
+
+    /// Look up a preset by name: `default`, `vip`, `chip48`, `schip` or `xochip`
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Quirks::default()),
+            "vip" => Some(Quirks::cosmac_vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" => Some(Quirks::schip()),
+            "xochip" => Some(Quirks::xochip()),
+            _ => None
+        }
+    }
+}
+
+impl Default for Quirks {
+    /// Shift VX in place, leave I unchanged, jump relative to V0, wrap sprites, leave VF untouched and
+    /// draw without waiting
+    fn default() -> Self {
+        Quirks {
+            shift_vy: false,
+            load_store_increment_i: false,
+            jump_vx: false,
+            clip_sprites: false,
+            vf_reset: false,
+            display_wait: false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset() {
+        assert_eq!(Quirks::preset("vip"), Some(Quirks::cosmac_vip()));
+        assert_eq!(Quirks::preset("xochip"), Some(Quirks::xochip()));
+        assert_eq!(Quirks::preset("default"), Some(Quirks::default()));
+        assert_eq!(Quirks::preset("unknown"), None);
+    }
+}
@@ -0,0 +1,90 @@
+//! Random numbers for the RND instruction
+
+/// Source of the random bytes used by the RND instruction
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+
+    /// State to store in save states. Sources that cannot be saved return nothing
+    fn get_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restore a state returned by `get_state`. Returns false, leaving the source untouched, if the state is invalid
+    fn set_state(&mut self, state: &[u8]) -> bool {
+        state.is_empty()
+    }
+}
+
+/// Seeded xorshift64* generator, the default random source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Xorshift {
+    state: u64
+}
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Self {
+        // xorshift needs a non-zero state, mix the seed so similar seeds give unrelated sequences
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+
+        Xorshift {
+            state: if z == 0 { 1 } else { z }
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+}
+
+impl RandomSource for Xorshift {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn get_state(&self) -> Vec<u8> {
+        (0..8).map(|i| (self.state >> (i * 8)) as u8).collect()
+    }
+
+    fn set_state(&mut self, state: &[u8]) -> bool {
+        if state.len() != 8 {
+            return false;
+        }
+
+        let value = (0..8).fold(0, |value, i| value | ((state[i] as u64) << (i * 8)));
+
+        if value == 0 {
+            return false;
+        }
+
+        self.state = value;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xorshift_state() {
+        let mut a = Xorshift::new(42);
+        a.next_byte();
+
+        let mut b = Xorshift::new(0);
+        assert!(b.set_state(&a.get_state()));
+
+        let expected: Vec<u8> = (0..16).map(|_| a.next_byte()).collect();
+        let actual: Vec<u8> = (0..16).map(|_| b.next_byte()).collect();
+        assert_eq!(actual, expected);
+
+        assert!(!b.set_state(&[0; 8]));
+        assert!(!b.set_state(&[1, 2, 3]));
+    }
+}
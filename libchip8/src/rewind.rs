@@ -0,0 +1,161 @@
+//! Rewind history
+//!
+//! Snapshots are save states. Only the newest one is kept whole, every older one is stored as the
+//! XOR of it with the snapshot after it, run length encoded. States a few frames apart differ in
+//! a handful of bytes, so a delta is mostly one long run of zeros.
+
+use super::*;
+use state::{read_u16, read_u32, write_u16, write_u32};
+
+use std::collections::VecDeque;
+
+/// Longest run of zeros or literal bytes in a delta
+const MAX_RUN: usize = 0xFFFF;
+
+/// Bounded history of machine states to rewind through
+pub struct RewindBuffer {
+    depth: usize,
+    newest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>> // oldest first, each restores the snapshot before the next one
+}
+
+impl RewindBuffer {
+    /// Create a history of up to `depth` snapshots, a depth of 0 keeps nothing
+    pub fn new(depth: usize) -> Self {
+        RewindBuffer {
+            depth: depth,
+            newest: None,
+            deltas: VecDeque::new()
+        }
+    }
+
+    /// Take a snapshot of the machine, dropping the oldest one if the history is full
+    pub fn push(&mut self, vm: &Chip8) {
+        if self.depth == 0 {
+            return;
+        }
+
+        let state = vm.save_state();
+
+        if let Some(newest) = self.newest.take() {
+            self.deltas.push_back(encode_delta(&newest, &state));
+
+            if self.deltas.len() >= self.depth {
+                self.deltas.pop_front();
+            }
+        }
+
+        self.newest = Some(state);
+    }
+
+    /// Restore the newest snapshot and remove it from the history. Returns false if it is empty
+    pub fn pop(&mut self, vm: &mut Chip8) -> bool {
+        let state = match self.newest.take() {
+            Some(state) => state,
+            None => return false
+        };
+
+        self.newest = self.deltas.pop_back().map(|delta| decode_delta(&delta, &state));
+
+        vm.load_state(&state).is_ok()
+    }
+
+    /// Number of snapshots in the history
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Bytes used by the snapshots
+    pub fn get_size(&self) -> usize {
+        self.newest.as_ref().map_or(0, |state| state.len()) + self.deltas.iter().map(|delta| delta.len()).sum::<usize>()
+    }
+}
+
+/// Encode `old` as the difference to `new`. The delta is the length of `old` followed by pairs of
+/// a zero run and a run of literal bytes, each with a `u16` length
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = old.iter().enumerate().map(|(i, b)| b ^ new.get(i).cloned().unwrap_or(0)).collect();
+
+    let mut delta = Vec::new();
+    write_u32(&mut delta, old.len() as u32);
+
+    let mut i = 0;
+
+    while i < xor.len() {
+        let zeros = xor[i..].iter().take(MAX_RUN).take_while(|b| **b == 0).count();
+        i += zeros;
+
+        let literals = xor[i..].iter().take(MAX_RUN).take_while(|b| **b != 0).count();
+
+        write_u16(&mut delta, zeros as u16);
+        write_u16(&mut delta, literals as u16);
+        delta.extend_from_slice(&xor[i..(i + literals)]);
+        i += literals;
+    }
+
+    delta
+}
+
+/// Recreate the state encoded by `encode_delta` from the newer state
+fn decode_delta(delta: &[u8], new: &[u8]) -> Vec<u8> {
+    let len = read_u32(delta) as usize;
+    let newer = |i: usize| new.get(i).cloned().unwrap_or(0);
+
+    let mut old = Vec::with_capacity(len);
+    let mut pos = 4;
+
+    while old.len() < len {
+        let zeros = read_u16(&delta[pos..]) as usize;
+        let literals = read_u16(&delta[(pos + 2)..]) as usize;
+        pos += 4;
+
+        for _ in 0..zeros {
+            let i = old.len();
+            old.push(newer(i));
+        }
+
+        for b in delta[pos..(pos + literals)].iter() {
+            let i = old.len();
+            old.push(b ^ newer(i));
+        }
+
+        pos += literals;
+    }
+
+    old
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_round_trip() {
+        let new: Vec<u8> = (0..200000).map(|i| (i % 251) as u8).collect();
+
+        let mut old = new.clone();
+        old[0] ^= 1;
+        old[70000] = 0;
+        old.truncate(150000);
+        old.extend_from_slice(&[1, 2, 3]);
+
+        let delta = encode_delta(&old, &new);
+        assert!(delta.len() < 100);
+        assert_eq!(decode_delta(&delta, &new), old);
+
+        assert_eq!(decode_delta(&encode_delta(&new, &old), &old), new);
+    }
+}
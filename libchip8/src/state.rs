@@ -0,0 +1,351 @@
+//! Save states
+//!
+//! A state is a 4 byte magic, a version byte and a list of tagged sections. Each section is a
+//! 4 byte tag, a little endian `u32` payload length and the payload itself.
+
+use super::*;
+
+use std::collections::HashMap;
+use std::error::Error;
+
+const MAGIC: &'static [u8; 4] = b"QZ8S";
+const VERSION: u8 = 1;
+
+const TAG_CPU: &'static [u8; 4] = b"CPU ";
+const TAG_STACK: &'static [u8; 4] = b"STCK";
+const TAG_MEMORY: &'static [u8; 4] = b"MEM ";
+const TAG_KEYS: &'static [u8; 4] = b"KEYS";
+const TAG_DISPLAY: &'static [u8; 4] = b"DISP";
+const TAG_SCHIP: &'static [u8; 4] = b"SCHP";
+const TAG_XOCHIP: &'static [u8; 4] = b"XOCH";
+const TAG_CLOCK: &'static [u8; 4] = b"CLK ";
+const TAG_RANDOM: &'static [u8; 4] = b"RNG ";
+const TAG_KEY_WAIT: &'static [u8; 4] = b"KWT ";
+const TAG_DISPLAY_WAIT: &'static [u8; 4] = b"DWT ";
+
+/// Marks an unused register or key in the key wait section
+const NONE: u8 = 0xFF;
+
+/// Reasons a save state could not be loaded
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    /// The data is not a save state
+    BadMagic,
+    /// The state was written by an incompatible version
+    UnsupportedVersion(u8),
+    /// The data ended in the middle of a section
+    Truncated,
+    /// A required section is missing
+    MissingSection(String),
+    /// A section has the wrong length for its contents
+    InvalidSection(String),
+    /// The state was saved from a machine with a different amount of memory
+    MemorySizeMismatch { expected: usize, found: usize }
+}
+
+impl<'a> Chip8<'a> {
+    /// Serialize the complete machine state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        state.extend_from_slice(MAGIC);
+        state.push(VERSION);
+
+        let mut cpu = Vec::new();
+        write_u16(&mut cpu, self.pc);
+        cpu.push(self.sp);
+        write_u16(&mut cpu, self.i);
+        cpu.extend_from_slice(&self.v);
+        cpu.push(self.dt);
+        cpu.push(self.st);
+        write_section(&mut state, TAG_CPU, &cpu);
+
+        let mut stack = Vec::new();
+        for addr in self.stack.iter() {
+            write_u16(&mut stack, *addr);
+        }
+        write_section(&mut state, TAG_STACK, &stack);
+
+        write_section(&mut state, TAG_MEMORY, &self.memory);
+
+        let keys: Vec<u8> = self.keys.iter().map(|k| *k as u8).collect();
+        write_section(&mut state, TAG_KEYS, &keys);
+
+        let mut display = vec![self.hires as u8, self.planes];
+        display.extend_from_slice(&self.pixels);
+        write_section(&mut state, TAG_DISPLAY, &display);
+
+        let mut schip = self.get_rpl_flags().to_vec();
+        schip.push(self.halted as u8);
+        write_section(&mut state, TAG_SCHIP, &schip);
+
+        let mut xochip = self.audio_pattern.to_vec();
+        xochip.push(self.pitch);
+        write_section(&mut state, TAG_XOCHIP, &xochip);
+
+        let mut clock = Vec::new();
+        write_u64(&mut clock, self.cycles);
+        write_u64(&mut clock, self.frames);
+        write_u32(&mut clock, self.frame_cycle);
+        write_section(&mut state, TAG_CLOCK, &clock);
+
+        write_section(&mut state, TAG_RANDOM, &self.rng.get_state());
+
+        let key_wait = match self.key_wait {
+            KeyWait::Idle => [NONE, NONE],
+            KeyWait::Press(x) => [x as u8, NONE],
+            KeyWait::Release(x, key) => [x as u8, key]
+        };
+        write_section(&mut state, TAG_KEY_WAIT, &key_wait);
+
+        write_section(&mut state, TAG_DISPLAY_WAIT, &[self.display_wait as u8]);
+
+        state
+    }
+
+    /// Restore a state created by `save_state`. The machine is left untouched if the state is invalid
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.len() < MAGIC.len() + 1 || &state[..MAGIC.len()] != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = state[MAGIC.len()];
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let sections = read_sections(&state[(MAGIC.len() + 1)..])?;
+
+        let cpu = get_section(&sections, TAG_CPU, 2 + 1 + 2 + NUM_REGISTERS + 2)?;
+        if cpu[2] as usize > STACK_SIZE {
+            return Err(StateError::InvalidSection("CPU".to_string()));
+        }
+        let stack = get_section(&sections, TAG_STACK, 2 * STACK_SIZE)?;
+        let memory = get_section(&sections, TAG_MEMORY, self.memory.len())
+            .map_err(|e| match e {
+                StateError::InvalidSection(_) => StateError::MemorySizeMismatch {
+                    expected: self.memory.len(),
+                    found: sections[TAG_MEMORY].len()
+                },
+                e => e
+            })?;
+        let keys = get_section(&sections, TAG_KEYS, NUM_KEYS)?;
+        let display = get_section(&sections, TAG_DISPLAY, 2 + NUM_PIXELS)?;
+        // the planes and pixel values index the four colour palette
+        if display[1..].iter().any(|value| *value > 3) {
+            return Err(StateError::InvalidSection("DISP".to_string()));
+        }
+        let num_flags = self.get_rpl_flags().len();
+        let schip = match get_section(&sections, TAG_SCHIP, num_flags + 1) {
+            // states from before XO-CHIP had sixteen flags only have eight
+            Err(StateError::InvalidSection(_)) if num_flags != NUM_RPL_FLAGS => get_section(&sections, TAG_SCHIP, NUM_RPL_FLAGS + 1)?,
+            result => result?
+        };
+        let (flags, halted) = schip.split_at(schip.len() - 1);
+        let xochip = get_section(&sections, TAG_XOCHIP, AUDIO_PATTERN_SIZE + 1)?;
+        // states from before the clock was saved do not have it
+        let clock = match get_section(&sections, TAG_CLOCK, 8 + 8 + 4) {
+            Ok(clock) => Some(clock),
+            Err(StateError::MissingSection(_)) => None,
+            Err(e) => return Err(e)
+        };
+        let key_wait = match get_section(&sections, TAG_KEY_WAIT, 2) {
+            Ok(key_wait) => match (key_wait[0], key_wait[1]) {
+                (NONE, NONE) => KeyWait::Idle,
+                (x, NONE) if (x as usize) < NUM_REGISTERS => KeyWait::Press(x as usize),
+                (x, key) if (x as usize) < NUM_REGISTERS && (key as usize) < NUM_KEYS => KeyWait::Release(x as usize, key),
+                _ => return Err(StateError::InvalidSection("KWT".to_string()))
+            },
+            Err(StateError::MissingSection(_)) => KeyWait::Idle,
+            Err(e) => return Err(e)
+        };
+        let display_wait = match get_section(&sections, TAG_DISPLAY_WAIT, 1) {
+            Ok(display_wait) => display_wait[0] != 0,
+            Err(StateError::MissingSection(_)) => false,
+            Err(e) => return Err(e)
+        };
+
+        // the random source validates its own state, restore it first so a bad state changes nothing
+        if let Some(rng) = sections.get(TAG_RANDOM) {
+            if !self.rng.set_state(rng) {
+                return Err(StateError::InvalidSection("RNG".to_string()));
+            }
+        }
+
+        // all sections are valid, apply them
+        self.pc = read_u16(&cpu[0..]);
+        self.sp = cpu[2];
+        self.i = read_u16(&cpu[3..]);
+        self.v.copy_from_slice(&cpu[5..(5 + NUM_REGISTERS)]);
+        self.dt = cpu[5 + NUM_REGISTERS];
+        self.st = cpu[6 + NUM_REGISTERS];
+
+        for (i, addr) in self.stack.iter_mut().enumerate() {
+            *addr = read_u16(&stack[(i * 2)..]);
+        }
+
+        self.memory.copy_from_slice(memory);
+
+        for (key, value) in self.keys.iter_mut().zip(keys.iter()) {
+            *key = *value != 0;
+        }
+
+        self.hires = display[0] != 0;
+        self.planes = display[1];
+        for (i, value) in display[2..].iter().enumerate() {
+            self.pixels[i] = *value;
+        }
+
+        self.set_rpl_flags(flags);
+        self.halted = halted[0] != 0;
+
+        self.audio_pattern.copy_from_slice(&xochip[..AUDIO_PATTERN_SIZE]);
+        self.pitch = xochip[AUDIO_PATTERN_SIZE];
+
+        if let Some(clock) = clock {
+            self.cycles = read_u64(&clock[0..]);
+            self.frames = read_u64(&clock[8..]);
+            self.frame_cycle = read_u32(&clock[16..]).min(self.instructions_per_frame - 1);
+        }
+
+        self.key_wait = key_wait;
+        self.display_wait = display_wait;
+
+        self.refresh_display();
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(v) => write!(f, "Unsupported save state version: {}", v),
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::MissingSection(ref tag) => write!(f, "Save state is missing section '{}'", tag),
+            StateError::InvalidSection(ref tag) => write!(f, "Save state section '{}' is invalid", tag),
+            StateError::MemorySizeMismatch { expected, found } => {
+                write!(f, "Save state has {} bytes of memory, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl Error for StateError {
+    fn description(&self) -> &str {
+        "invalid save state"
+    }
+}
+
+fn write_section(state: &mut Vec<u8>, tag: &[u8; 4], payload: &[u8]) {
+    state.extend_from_slice(tag);
+    write_u32(state, payload.len() as u32);
+    state.extend_from_slice(payload);
+}
+
+fn read_sections(mut data: &[u8]) -> Result<HashMap<&[u8; 4], &[u8]>, StateError> {
+    let mut sections = HashMap::new();
+
+    while !data.is_empty() {
+        if data.len() < 8 {
+            return Err(StateError::Truncated);
+        }
+
+        let tag = section_tag(&data[..4]);
+        let len = read_u32(&data[4..]) as usize;
+        data = &data[8..];
+
+        if data.len() < len {
+            return Err(StateError::Truncated);
+        }
+
+        // sections from newer versions that are not known are ignored
+        if let Some(tag) = tag {
+            sections.insert(tag, &data[..len]);
+        }
+
+        data = &data[len..];
+    }
+
+    Ok(sections)
+}
+
+/// Map a tag read from a state onto one of the known tags
+fn section_tag(tag: &[u8]) -> Option<&'static [u8; 4]> {
+    let known = [TAG_CPU, TAG_STACK, TAG_MEMORY, TAG_KEYS, TAG_DISPLAY, TAG_SCHIP, TAG_XOCHIP, TAG_CLOCK, TAG_RANDOM, TAG_KEY_WAIT, TAG_DISPLAY_WAIT];
+    known.iter().find(|known| &known[..] == tag).cloned()
+}
+
+fn get_section<'s>(sections: &HashMap<&[u8; 4], &'s [u8]>, tag: &[u8; 4], len: usize) -> Result<&'s [u8], StateError> {
+    let name = String::from_utf8_lossy(tag).trim().to_string();
+
+    match sections.get(tag) {
+        Some(section) if section.len() == len => Ok(section),
+        Some(_) => Err(StateError::InvalidSection(name)),
+        None => Err(StateError::MissingSection(name))
+    }
+}
+
+pub fn write_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.push(value as u8);
+    buf.push((value >> 8) as u8);
+}
+
+pub fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    for i in 0..4 {
+        buf.push((value >> (i * 8)) as u8);
+    }
+}
+
+pub fn write_u64(buf: &mut Vec<u8>, value: u64) {
+    for i in 0..8 {
+        buf.push((value >> (i * 8)) as u8);
+    }
+}
+
+pub fn read_u16(buf: &[u8]) -> u16 {
+    (buf[0] as u16) | ((buf[1] as u16) << 8)
+}
+
+pub fn read_u32(buf: &[u8]) -> u32 {
+    (0..4).fold(0, |value, i| value | ((buf[i] as u32) << (i * 8)))
+}
+
+pub fn read_u64(buf: &[u8]) -> u64 {
+    (0..8).fold(0, |value, i| value | ((buf[i] as u64) << (i * 8)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_write_u32() {
+        let mut buf = Vec::new();
+        write_u32(&mut buf, 0xDEADBEEF);
+
+        assert_eq!(buf, vec![0xEF, 0xBE, 0xAD, 0xDE]);
+        assert_eq!(read_u32(&buf), 0xDEADBEEF);
+    }
+
+    #[test]
+    fn test_unknown_sections_are_skipped() {
+        let mut data = Vec::new();
+        write_section(&mut data, b"NEW!", &[1, 2, 3]);
+        write_section(&mut data, TAG_KEYS, &[0; NUM_KEYS]);
+
+        let sections = read_sections(&data).unwrap();
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[TAG_KEYS].len(), NUM_KEYS);
+    }
+
+    #[test]
+    fn test_truncated_section() {
+        let mut data = Vec::new();
+        write_section(&mut data, TAG_KEYS, &[0; NUM_KEYS]);
+        data.pop();
+
+        assert_eq!(read_sections(&data), Err(StateError::Truncated));
+    }
+}
@@ -0,0 +1,105 @@
+//! Execution tracing
+//!
+//! A tracer set with `Chip8::set_tracer` is called with a `TraceEntry` for every executed
+//! instruction. Entries can be written as compact text lines, or as fixed size binary records for
+//! other tools.
+
+use super::*;
+use state::{read_u16, read_u64, write_u16, write_u64};
+
+/// Size of an entry written by `TraceEntry::to_bytes`
+pub const TRACE_ENTRY_SIZE: usize = 8 + 2 + 2 + 2 + 2 * (NUM_REGISTERS + 2);
+
+/// The V registers and I
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Registers {
+    pub v: [u8; NUM_REGISTERS],
+    pub i: u16
+}
+
+/// An executed instruction and the registers before and after it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEntry {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    /// Word following the opcode, the address of a XO-CHIP long load
+    pub operand: u16,
+    pub instruction: Instruction,
+    pub before: Registers,
+    pub after: Registers
+}
+
+impl TraceEntry {
+    /// Format the entry as a line with the registers before the instruction and the ones it changed
+    ///
+    /// `0204 A22A LD I, 0x22A          I=0204 V=05000000000000000000000000000000 -> I=022A`
+    pub fn to_text(&self) -> String {
+        let v: Vec<String> = self.before.v.iter().map(|x| format!("{:02X}", x)).collect();
+
+        let mut changes: Vec<String> = (0..NUM_REGISTERS)
+            .filter(|&x| self.before.v[x] != self.after.v[x])
+            .map(|x| format!("V{:X}={:02X}", x, self.after.v[x]))
+            .collect();
+
+        if self.before.i != self.after.i {
+            changes.push(format!("I={:04X}", self.after.i));
+        }
+
+        let mut line = format!("{:04X} {:04X} {:<20} I={:04X} V={}", self.pc, self.opcode, self.instruction.to_string(), self.before.i, v.concat());
+
+        if !changes.is_empty() {
+            line.push_str(" -> ");
+            line.push_str(&changes.join(" "));
+        }
+
+        line
+    }
+
+    /// Serialize the entry into `TRACE_ENTRY_SIZE` bytes: the cycle, pc, opcode and operand, then
+    /// the V registers and I before and after, little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(TRACE_ENTRY_SIZE);
+        write_u64(&mut data, self.cycle);
+        write_u16(&mut data, self.pc);
+        write_u16(&mut data, self.opcode);
+        write_u16(&mut data, self.operand);
+
+        for registers in [self.before, self.after].iter() {
+            data.extend_from_slice(&registers.v);
+            write_u16(&mut data, registers.i);
+        }
+
+        data
+    }
+
+    /// Read an entry written by `to_bytes`
+    pub fn from_bytes(data: &[u8]) -> Option<TraceEntry> {
+        if data.len() < TRACE_ENTRY_SIZE {
+            return None;
+        }
+
+        let opcode = read_u16(&data[10..]);
+        let operand = read_u16(&data[12..]);
+
+        let registers = |offset: usize| {
+            let mut v = [0; NUM_REGISTERS];
+            v.copy_from_slice(&data[offset..(offset + NUM_REGISTERS)]);
+
+            Registers {
+                v: v,
+                i: read_u16(&data[(offset + NUM_REGISTERS)..])
+            }
+        };
+
+        Instruction::decode(opcode, operand).ok().map(|instruction| TraceEntry {
+            cycle: read_u64(&data[0..]),
+            pc: read_u16(&data[8..]),
+            opcode: opcode,
+            operand: operand,
+            instruction: instruction,
+            before: registers(14),
+            after: registers(14 + NUM_REGISTERS + 2)
+        })
+    }
+}
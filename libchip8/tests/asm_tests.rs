@@ -0,0 +1,162 @@
+extern crate libchip8;
+
+use libchip8::*;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+
+#[test]
+fn test_assemble_instructions() {
+    let source = "
+        CLS
+        LD V0, 0x05       ; comment
+        ld v1, $0A
+        ADD V0, V1
+        SHR V2
+        DRW V0, V1, 5
+        LD [I], V3
+        LD I, LONG 0x1234
+        SAVE V2 - V4
+        LD PITCH, V5
+    ";
+
+    let rom = assemble(source).unwrap();
+
+    assert_eq!(rom, vec![
+        0x00, 0xE0,
+        0x60, 0x05,
+        0x61, 0x0A,
+        0x80, 0x14,
+        0x82, 0x26,
+        0xD0, 0x15,
+        0xF3, 0x55,
+        0xF0, 0x00, 0x12, 0x34,
+        0x52, 0x42,
+        0xF5, 0x3A
+    ]);
+}
+
+#[test]
+fn test_assemble_labels_and_data() {
+    let source = "
+        define SPEED 3
+        start:
+            CALL draw
+            JP start
+        draw:
+            LD I, sprite
+            ADD V0, SPEED + 1
+            RET
+        sprite: db 0xF0, %10010000, 0x90
+                dw 0xABCD
+    ";
+
+    let rom = assemble(source).unwrap();
+
+    assert_eq!(rom, vec![
+        0x22, 0x04,
+        0x12, 0x00,
+        0xA2, 0x0A,
+        0x70, 0x04,
+        0x00, 0xEE,
+        0xF0, 0x90, 0x90,
+        0xAB, 0xCD
+    ]);
+}
+
+#[test]
+fn test_assemble_macro() {
+    let source = "
+        macro move x, dx
+            ADD x, dx
+            AND x, VF
+        endm
+
+        move V1, 2
+        move V2, 0xFF
+    ";
+
+    let rom = assemble(source).unwrap();
+
+    assert_eq!(rom, vec![0x71, 0x02, 0x81, 0xF2, 0x72, 0xFF, 0x82, 0xF2]);
+}
+
+#[test]
+fn test_assemble_include() {
+    let dir = env::temp_dir().join("libchip8_asm_include");
+    fs::create_dir_all(&dir).unwrap();
+
+    File::create(dir.join("main.asm")).unwrap()
+        .write_all(b"JP end\ninclude \"sprites.asm\"\nend: EXIT\n").unwrap();
+    File::create(dir.join("sprites.asm")).unwrap()
+        .write_all(b"sprite: db 0x3C, 0x42\n").unwrap();
+
+    let rom = assemble_file(dir.join("main.asm")).unwrap();
+
+    assert_eq!(rom, vec![0x12, 0x04, 0x3C, 0x42, 0x00, 0xFD]);
+}
+
+#[test]
+fn test_assemble_errors() {
+    let error = assemble("CLS\n  LD V0, missing\n").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 10));
+    assert_eq!(error.to_string(), "<input>:2:10: Undefined symbol 'missing'");
+
+    let error = assemble("  FOO V1").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 3));
+
+    let error = assemble("LD V0, 256").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 8));
+
+    let error = assemble("SKP 1").unwrap_err();
+    assert_eq!(error.message, "Invalid operands for SKP");
+
+    let error = assemble("a:\na:").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 1));
+
+    let error = assemble("LD V0, 0x7FFFFFFFFFFFFFFF + 1").unwrap_err();
+    assert_eq!(error.message, "Value '0x7FFFFFFFFFFFFFFF + 1' is out of range");
+
+    let error = assemble("LD I, abcé").unwrap_err();
+    assert_eq!(error.message, "Undefined symbol 'abcé'");
+}
+
+#[test]
+fn test_assemble_listing_round_trip() {
+    let rom = vec![
+        0x60, 0x00, // LD V0, 0x00
+        0x22, 0x08, // CALL 0x208
+        0x12, 0x02, // JP 0x202
+        0xFF, 0xFF, // data
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x05, // DRW V0, V0, 5
+        0x00, 0xEE, // RET
+        0xAB
+    ];
+
+    assert_eq!(assemble(&listing(&rom, 0x200)).unwrap(), rom);
+}
+
+#[test]
+fn test_assemble_listing_every_opcode() {
+    for opcode in 0..0x10000u32 {
+        let rom = vec![(opcode >> 8) as u8, opcode as u8];
+
+        assert_eq!(assemble(&listing(&rom, 0x200)).unwrap(), rom, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn test_encode_decode() {
+    for opcode in 0..0x10000u32 {
+        let opcode = opcode as u16;
+
+        if let Ok(instr) = Instruction::decode(opcode, 0xBEEF) {
+            let bytes = instr.encode();
+            let encoded = ((bytes[0] as u16) << 8) | bytes[1] as u16;
+
+            assert_eq!(Instruction::decode(encoded, 0xBEEF).unwrap(), instr);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+extern crate libchip8;
+
+use libchip8::*;
+use libchip8::debugger::*;
+
+fn program() -> Vec<u8> {
+    vec![
+        0x60, 0x01, // LD V0, $01
+        0x22, 0x0A, // CALL $20A
+        0x70, 0x01, // ADD V0, $01
+        0x12, 0x06, // JP $206
+        0x00, 0x00,
+        0x70, 0x10, // ADD V0, $10
+        0xA3, 0x00, // LD I, $300
+        0xF0, 0x55, // LD [I], V0
+        0x00, 0xEE  // RET
+    ]
+}
+
+fn setup<'a>() -> (Chip8<'a>, Debugger) {
+    let mut vm = Chip8::new(0.00001);
+    vm.load_memory(program()).unwrap();
+
+    let mut debugger = Debugger::new();
+    debugger.attach(&mut vm);
+
+    (vm, debugger)
+}
+
+#[test]
+fn test_breakpoint() {
+    let (mut vm, mut debugger) = setup();
+    debugger.add_breakpoint(0x20C);
+
+    assert_eq!(debugger.run(&mut vm, 100).unwrap(), StopReason::Breakpoint(0x20C));
+    assert_eq!(vm.get_pc(), 0x20C);
+    // the instruction at the breakpoint has not run yet
+    assert_eq!(vm.get_register(0), 0x11);
+    assert_eq!(vm.get_i(), 0);
+
+    // resuming runs past the breakpoint
+    assert_eq!(debugger.run(&mut vm, 5).unwrap(), StopReason::StepLimit);
+    assert_eq!(vm.get_i(), 0x300);
+}
+
+#[test]
+fn test_remove_breakpoint() {
+    let (mut vm, mut debugger) = setup();
+    debugger.add_breakpoint(0x20C);
+
+    assert!(debugger.remove_breakpoint(0x20C));
+    assert!(!debugger.remove_breakpoint(0x20C));
+    assert_eq!(debugger.run(&mut vm, 100).unwrap(), StopReason::StepLimit);
+}
+
+#[test]
+fn test_write_watchpoint() {
+    let (mut vm, mut debugger) = setup();
+    debugger.add_watchpoint(0x300, Watch::Write);
+
+    let expected = StopReason::Watchpoint(MemoryAccess { addr: 0x300, access: Access::Write });
+
+    assert_eq!(debugger.run(&mut vm, 100).unwrap(), expected);
+    assert_eq!(vm.get_pc(), 0x210);
+}
+
+#[test]
+fn test_read_watchpoint_ignores_writes() {
+    let (mut vm, mut debugger) = setup();
+    debugger.add_watchpoint(0x300, Watch::Read);
+
+    assert_eq!(debugger.run(&mut vm, 100).unwrap(), StopReason::StepLimit);
+}
+
+#[test]
+fn test_register_condition() {
+    let (mut vm, mut debugger) = setup();
+
+    let index = debugger.add_condition(RegisterCondition {
+        register: 0,
+        comparison: Comparison::Greater,
+        value: 0x11
+    });
+
+    assert_eq!(debugger.run(&mut vm, 100).unwrap(), StopReason::Condition(index));
+    assert_eq!(vm.get_register(0), 0x12);
+
+    // the condition stays true and does not trigger again
+    assert_eq!(debugger.run(&mut vm, 5).unwrap(), StopReason::StepLimit);
+}
+
+#[test]
+fn test_step_over() {
+    let (mut vm, mut debugger) = setup();
+
+    assert_eq!(debugger.step(&mut vm).unwrap(), StopReason::Step);
+    assert_eq!(debugger.step_over(&mut vm).unwrap(), StopReason::Step);
+    assert_eq!(vm.get_pc(), 0x204);
+    assert_eq!(vm.get_sp(), 0);
+    assert_eq!(vm.get_register(0), 0x11);
+}
+
+#[test]
+fn test_step_over_stops_at_breakpoint() {
+    let (mut vm, mut debugger) = setup();
+    debugger.add_breakpoint(0x20E);
+
+    debugger.step(&mut vm).unwrap();
+
+    assert_eq!(debugger.step_over(&mut vm).unwrap(), StopReason::Breakpoint(0x20E));
+}
+
+#[test]
+fn test_step_out() {
+    let (mut vm, mut debugger) = setup();
+
+    debugger.step(&mut vm).unwrap();
+    debugger.step(&mut vm).unwrap();
+
+    assert_eq!(vm.get_sp(), 1);
+    assert_eq!(debugger.step_out(&mut vm).unwrap(), StopReason::Step);
+    assert_eq!(vm.get_pc(), 0x204);
+    assert_eq!(vm.get_sp(), 0);
+}
+
+#[test]
+fn test_halted() {
+    let mut vm = Chip8::new(0.00001);
+    vm.load_memory(vec![0x00, 0xFD]).unwrap();
+
+    let mut debugger = Debugger::new();
+
+    assert_eq!(debugger.step(&mut vm).unwrap(), StopReason::Halted);
+    assert_eq!(debugger.run(&mut vm, 10).unwrap(), StopReason::Halted);
+}
+
+#[test]
+fn test_run_frame() {
+    let (mut vm, mut debugger) = setup();
+    vm.set_instructions_per_frame(2);
+    debugger.add_breakpoint(0x20C);
+
+    // the frame ends before the breakpoint is reached
+    assert_eq!(debugger.run_frame(&mut vm).unwrap(), None);
+    assert_eq!(vm.get_frames(), 1);
+
+    assert_eq!(debugger.run_frame(&mut vm).unwrap(), Some(StopReason::Breakpoint(0x20C)));
+    assert_eq!(vm.get_frames(), 1);
+}
@@ -0,0 +1,71 @@
+extern crate libchip8;
+
+use libchip8::*;
+
+#[test]
+fn test_mnemonics() {
+    let program = vec![
+        0x33, 0x0C, // SE V3, $0C
+        0x8A, 0xB4, // ADD VA, VB
+        0xD1, 0x25, // DRW V1, V2, 5
+        0xF4, 0x65, // LD V4, [I]
+        0x00, 0xFF, // HIGH
+        0xF0, 0x00, // LD I, LONG $1234
+        0x12, 0x34,
+        0x52, 0x43  // LOAD V2 - V4
+    ];
+
+    let text: Vec<String> = disassemble(&program, 0x200).iter().map(|&(_, i)| i.to_string()).collect();
+
+    assert_eq!(text, vec![
+        "SE V3, 0x0C",
+        "ADD VA, VB",
+        "DRW V1, V2, 5",
+        "LD V4, [I]",
+        "HIGH",
+        "LD I, LONG 0x1234",
+        "LOAD V2 - V4"
+    ]);
+}
+
+#[test]
+fn test_disassemble_addresses() {
+    let program = vec![
+        0x00, 0xE0, // CLS
+        0xFF, 0xFF, // invalid, skipped
+        0xF0, 0x00, // LD I, LONG $1234
+        0x12, 0x34,
+        0x00, 0xEE  // RET
+    ];
+
+    let instructions = disassemble(&program, 0x200);
+
+    assert_eq!(instructions, vec![
+        (0x200, Instruction::CLS()),
+        (0x204, Instruction::LDILONG(0x1234)),
+        (0x208, Instruction::RET())
+    ]);
+}
+
+#[test]
+fn test_listing() {
+    let program = vec![
+        0x22, 0x06, // CALL $206
+        0x12, 0x02, // JP $202
+        0xFF, 0xFF, // data
+        0x00, 0xEE, // RET
+        0x13, 0x00, // JP $300, outside of the program
+        0xAB        // data
+    ];
+
+    let expected = "    CALL L206            ; 200\n\
+                    L202:\n\
+                    \x20   JP L202              ; 202\n\
+                    \x20   dw 0xFFFF            ; 204\n\
+                    L206:\n\
+                    \x20   RET                  ; 206\n\
+                    \x20   JP 0x300             ; 208\n\
+                    \x20   db 0xAB              ; 20A\n";
+
+    assert_eq!(listing(&program, 0x200), expected);
+}
@@ -2,9 +2,11 @@ extern crate libchip8;
 
 use libchip8::*;
 
+use std::time::{Duration, Instant};
+
 /// Helper function to help test
 fn run(vm: &mut Chip8, memory: Vec<u8>, should_panic: bool) {
-    vm.load_memory(memory);
+    vm.load_memory(memory).unwrap();
 
     loop {
         match vm.step() {
@@ -44,7 +46,7 @@ fn test_jump() {
 
     run(&mut vm, program, false);
 
-    assert_eq!(vm.get_pc(), 0x452u16);
+    assert_eq!(vm.get_pc(), 0x450u16);
 }
 
 #[test]
@@ -58,7 +60,7 @@ fn test_call() {
 
     run(&mut vm, program, false);
 
-    assert_eq!(vm.get_pc(), 0x252u16);
+    assert_eq!(vm.get_pc(), 0x250u16);
     assert_eq!(vm.get_sp(), 1);
     assert_eq!(vm.get_stack(0), 0x0202);
 }
@@ -114,7 +116,7 @@ fn test_skip_if_vx_equals_kk() {
 
     run(&mut vm, program, false);
 
-    assert_eq!(vm.get_pc(), 0x20A);
+    assert_eq!(vm.get_pc(), 0x208);
     assert_eq!(vm.get_register(0), 0xDE);
     assert_eq!(vm.get_register(1), 0xAD);
 }
@@ -133,7 +135,7 @@ fn test_skip_if_vx_equals_vy() {
 
     run(&mut vm, program, false);
 
-    assert_eq!(vm.get_pc(), 0x20A);
+    assert_eq!(vm.get_pc(), 0x208);
     assert_eq!(vm.get_register(0), 0xDE);
     assert_eq!(vm.get_register(1), 0xDE);
 }
@@ -151,7 +153,7 @@ fn test_skip_if_vx_not_equals_kk() {
 
     run(&mut vm, program, false);
 
-    assert_eq!(vm.get_pc(), 0x208);
+    assert_eq!(vm.get_pc(), 0x206);
     assert_eq!(vm.get_register(0), 0xDE);
 }
 
@@ -169,7 +171,7 @@ fn test_skip_if_vx_not_equals_vy() {
 
     run(&mut vm, program, false);
 
-    assert_eq!(vm.get_pc(), 0x20A);
+    assert_eq!(vm.get_pc(), 0x208);
 }
 
 #[test]
@@ -361,7 +363,7 @@ fn test_jump_relative() {
 
     run(&mut vm, program, false);
 
-    assert_eq!(vm.get_pc(), 0x253);
+    assert_eq!(vm.get_pc(), 0x251);
 }
 
 #[test]
@@ -403,19 +405,42 @@ fn test_skip_if_key_not_pressed() {
 #[test]
 fn test_key_wait() {
     let mut vm = Chip8::new(0.00001);
-    vm.set_key_wait(Box::new(|| {
-        4
-    }));
+    vm.set_instructions_per_frame(10);
 
     let program = vec![
         0x60, 0x00, // LD V0, $00
+        0x61, 0x05, // LD V1, $05
+        0xF1, 0x15, // LD DT, V1
         0xF0, 0x0A, // LD Vx, K
         0xFF, 0xFF  // stop
     ];
 
-    run(&mut vm, program, false);
+    vm.load_memory(program).unwrap();
+    vm.key(2, true);
 
+    for _ in 0..4 {
+        vm.step().unwrap();
+    }
+
+    // the machine waits, the timers keep running
+    assert!(vm.is_waiting_for_key());
+    assert_eq!(vm.get_pc(), 0x208);
+
+    vm.run_for(20).unwrap();
+    assert!(vm.is_waiting_for_key());
+    assert_eq!(vm.get_dt(), 3);
+
+    // a key held before the wait does not count, the wait ends when a pressed key is released
+    vm.key(2, false);
+    vm.key(4, true);
+    vm.key(7, true);
+    vm.key(7, false);
+    assert!(vm.is_waiting_for_key());
+
+    vm.key(4, false);
+    assert!(!vm.is_waiting_for_key());
     assert_eq!(vm.get_register(0), 4);
+    assert!(vm.step().is_err());
 }
 
 #[test]
@@ -447,3 +472,781 @@ fn test_add_overflow() {
 
     assert_eq!(vm.get_register(0), 0x00);
 }
+
+#[test]
+fn test_draw_collision() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x05, // DRW V0, V0, 5
+        0x81, 0xF0, // LD V1, VF
+        0xD0, 0x05, // DRW V0, V0, 5
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_register(1), 0);
+    assert_eq!(vm.get_register(15), 1);
+    assert_eq!(vm.get_pixel(0, 0), (0, 0, 0));
+}
+
+#[test]
+fn test_hires() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x00, 0xFF, // HIGH
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert!(vm.is_hires());
+    assert_eq!(vm.get_display_width(), 128);
+    assert_eq!(vm.get_display_height(), 64);
+    assert_eq!(vm.get_display_memory().len(), 128 * 64 * 3);
+}
+
+#[test]
+fn test_lores() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x00, 0xFF, // HIGH
+        0x00, 0xFE, // LOW
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert!(!vm.is_hires());
+    assert_eq!(vm.get_display_memory().len(), 64 * 32 * 3);
+}
+
+#[test]
+fn test_draw_16x16() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x00, 0xFF, // HIGH
+        0x60, 0x00, // LD V0, $00
+        0xA2, 0x0A, // LD I, $20A
+        0xD0, 0x00, // DRW V0, V0, 0
+        0xFF, 0xFF, // stop
+        0x80, 0x01  // sprite row 0, remaining rows are zero
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pixel(0, 0), (255, 255, 255));
+    assert_eq!(vm.get_pixel(15, 0), (255, 255, 255));
+    assert_eq!(vm.get_pixel(16, 0), (0, 0, 0));
+}
+
+#[test]
+fn test_scroll_down() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x00, 0xC2, // SCD 2
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pixel(0, 0), (0, 0, 0));
+    assert_eq!(vm.get_pixel(0, 2), (255, 255, 255));
+}
+
+#[test]
+fn test_scroll_left_right() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x00, 0xFB, // SCR
+        0x00, 0xFB, // SCR
+        0x00, 0xFC, // SCL
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pixel(3, 0), (0, 0, 0));
+    assert_eq!(vm.get_pixel(4, 0), (255, 255, 255));
+    assert_eq!(vm.get_pixel(7, 0), (255, 255, 255));
+    assert_eq!(vm.get_pixel(8, 0), (0, 0, 0));
+}
+
+#[test]
+fn test_ld_hf() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x02, // LD V0, $02
+        0xF0, 0x30, // LD HF, V0
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_i(), 0x50 + 20);
+}
+
+#[test]
+fn test_rpl_flags() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x01, // LD V0, $01
+        0x61, 0x02, // LD V1, $02
+        0xF1, 0x75, // LD R, V1
+        0x60, 0x00, // LD V0, $00
+        0x61, 0x00, // LD V1, $00
+        0xF1, 0x85, // LD V1, R
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_rpl_flag(0), 1);
+    assert_eq!(vm.get_rpl_flag(1), 2);
+    assert_eq!(vm.get_register(0), 1);
+    assert_eq!(vm.get_register(1), 2);
+}
+
+#[test]
+fn test_set_rpl_flags() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_rpl_flags(&[7, 8]);
+
+    let program = vec![
+        0xF1, 0x85, // LD V1, R
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_register(0), 7);
+    assert_eq!(vm.get_register(1), 8);
+    assert_eq!(vm.get_rpl_flags(), &[7, 8, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_xochip_rpl_flags() {
+    let mut vm = Chip8::with_memory_size(0.00001, XO_MEMORY_SIZE);
+
+    let program = vec![
+        0x6F, 0x0F, // LD VF, $0F
+        0xFF, 0x75, // LD R, VF
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_rpl_flags().len(), 16);
+    assert_eq!(vm.get_rpl_flag(15), 0x0F);
+
+    // SCHIP only has flags for V0 - V7
+    let mut vm = Chip8::new(0.00001);
+    vm.load_memory(vec![0xF8, 0x75]).unwrap(); // LD R, V8
+
+    assert_eq!(vm.step().unwrap_err().kind, ErrorKind::InvalidOpcode);
+    assert_eq!(vm.get_rpl_flags().len(), 8);
+}
+
+#[test]
+fn test_exit() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x00, 0xFD, // EXIT
+        0xFF, 0xFF  // stop
+    ];
+
+    vm.load_memory(program).unwrap();
+    vm.step().unwrap();
+    vm.step().unwrap();
+
+    assert!(vm.is_halted());
+    assert_eq!(vm.get_pc(), 0x202);
+}
+
+#[test]
+fn test_xo_memory_size() {
+    let mut vm = Chip8::with_memory_size(0.00001, XO_MEMORY_SIZE);
+
+    let mut program = vec![0; 0x8000];
+    program[0] = 0x14; // JP $400
+    program[1] = 0x00;
+
+    vm.load_memory(program).unwrap();
+    vm.step().unwrap();
+
+    assert_eq!(vm.get_memory_size(), 65536);
+    assert_eq!(vm.get_pc(), 0x400);
+}
+
+#[test]
+fn test_load_i_long() {
+    let mut vm = Chip8::with_memory_size(0.00001, XO_MEMORY_SIZE);
+
+    let program = vec![
+        0xF0, 0x00, // LD I, LONG $BEEF
+        0xBE, 0xEF,
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_i(), 0xBEEF);
+    assert_eq!(vm.get_pc(), 0x204);
+}
+
+#[test]
+fn test_load_i_long_past_end_of_memory() {
+    let mut vm = Chip8::new(0.00001);
+
+    // the long load is in the last word of memory, its operand would be past the end
+    let mut program = vec![0; MEMORY_SIZE - 0x200];
+    program[0] = 0x1F; // JP $FFE
+    program[1] = 0xFE;
+    program[MEMORY_SIZE - 0x202] = 0xF0;
+
+    vm.load_memory(program).unwrap();
+    vm.step().unwrap();
+
+    assert_eq!(vm.step().unwrap_err().kind, ErrorKind::AddressOutOfRange(0x1001));
+    assert_eq!(vm.get_i(), 0);
+}
+
+#[test]
+fn test_skip_load_i_long() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x30, 0x00, // SE V0, $00
+        0xF0, 0x00, // LD I, LONG $BEEF ; should skip
+        0xBE, 0xEF,
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_i(), 0);
+    assert_eq!(vm.get_pc(), 0x206);
+}
+
+#[test]
+fn test_save_load_range() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x62, 0x01, // LD V2, $01
+        0x63, 0x02, // LD V3, $02
+        0x64, 0x03, // LD V4, $03
+        0xA3, 0x00, // LD I, $300
+        0x52, 0x42, // SAVE V2 - V4
+        0x57, 0x53, // LOAD V7 - V5
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_i(), 0x300);
+    assert_eq!(vm.get_register(7), 1);
+    assert_eq!(vm.get_register(6), 2);
+    assert_eq!(vm.get_register(5), 3);
+}
+
+#[test]
+fn test_draw_planes() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0xF3, 0x01, // PLANE 3
+        0xA2, 0x0C, // LD I, $20C
+        0xD0, 0x01, // DRW V0, V0, 1
+        0xF2, 0x01, // PLANE 2
+        0xFF, 0xFF, // stop
+        0x80,       // plane 1 sprite
+        0xC0        // plane 2 sprite
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_planes(), 2);
+    assert_eq!(vm.get_pixel(0, 0), (85, 85, 85));
+    assert_eq!(vm.get_pixel(1, 0), (170, 170, 170));
+    assert_eq!(vm.get_pixel(2, 0), (0, 0, 0));
+
+    assert_eq!(vm.get_pixel_value(0, 0), 3);
+    assert_eq!(vm.get_pixel_value(1, 0), 2);
+    assert_eq!(&vm.get_pixel_values()[..3], &[3, 2, 0]);
+    assert_eq!(vm.get_pixel_values().len(), 64 * 32);
+}
+
+#[test]
+fn test_clear_selected_plane() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0xF3, 0x01, // PLANE 3
+        0xA2, 0x10, // LD I, $210
+        0xD0, 0x01, // DRW V0, V0, 1
+        0xF1, 0x01, // PLANE 1
+        0x00, 0xE0, // CLS
+        0xFF, 0xFF, // stop
+        0x00, 0x00,
+        0x80,       // plane 1 sprite
+        0x80        // plane 2 sprite
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pixel(0, 0), (170, 170, 170));
+}
+
+#[test]
+fn test_scroll_up() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0x61, 0x04, // LD V1, $04
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x11, // DRW V0, V1, 1
+        0x00, 0xD3, // SCU 3
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pixel(0, 4), (0, 0, 0));
+    assert_eq!(vm.get_pixel(0, 1), (255, 255, 255));
+}
+
+#[test]
+fn test_audio_pattern_and_pitch() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x70, // LD V0, $70
+        0xF0, 0x3A, // PITCH V0
+        0xA0, 0x00, // LD I, $000
+        0xF0, 0x02, // AUDIO
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pitch(), 0x70);
+    assert_eq!(vm.get_audio_pattern()[0], 0xF0);
+    assert_eq!(vm.get_audio_pattern().len(), 16);
+}
+
+#[test]
+fn test_store_load_registers() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x01, // LD V0, $01
+        0x61, 0x02, // LD V1, $02
+        0xA3, 0x00, // LD I, $300
+        0xF1, 0x55, // LD [I], V1
+        0xF1, 0x65, // LD V1, [I]
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_register(0), 1);
+    assert_eq!(vm.get_register(1), 2);
+    assert_eq!(vm.get_i(), 0x300);
+}
+
+#[test]
+fn test_quirk_load_store_increment_i() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_quirks(Quirks::cosmac_vip());
+
+    let program = vec![
+        0xA3, 0x00, // LD I, $300
+        0xF1, 0x55, // LD [I], V1
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_i(), 0x302);
+}
+
+#[test]
+fn test_quirk_shift_vy() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_quirks(Quirks::cosmac_vip());
+
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0x61, 0x81, // LD V1, $81
+        0x80, 0x1E, // SHL V0, V1
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_register(0), 0x02);
+    assert_eq!(vm.get_register(1), 0x81);
+    assert_eq!(vm.get_register(15), 1);
+}
+
+#[test]
+fn test_quirk_display_wait() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_quirks(Quirks::cosmac_vip());
+    vm.set_instructions_per_frame(10);
+
+    let program = vec![
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x60, 0x01, // LD V0, $01
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x61, 0x01  // LD V1, $01
+    ];
+
+    vm.load_memory(program).unwrap();
+    vm.run_for(5).unwrap();
+
+    // the rest of the frame is spent waiting
+    assert!(vm.is_waiting_for_display());
+    assert_eq!(vm.get_pc(), 0x202);
+
+    vm.run_frame().unwrap();
+    assert!(!vm.is_waiting_for_display());
+
+    vm.run_frame().unwrap();
+    assert_eq!(vm.get_pc(), 0x206);
+    assert_eq!(vm.get_register(0), 1);
+    assert_eq!(vm.get_register(1), 0);
+}
+
+#[test]
+fn test_quirk_jump_vx() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_quirks(Quirks::schip());
+
+    let program = vec![
+        0x60, 0x01, // LD V0, $01
+        0x62, 0x04, // LD V2, $04
+        0xB2, 0x50, // JP V2, $250
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pc(), 0x254);
+}
+
+#[test]
+fn test_quirk_vf_reset() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_quirks(Quirks::cosmac_vip());
+
+    let program = vec![
+        0x6F, 0x01, // LD VF, $01
+        0x80, 0x11, // OR V0, V1
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_register(15), 0);
+}
+
+#[test]
+fn test_quirk_clip_sprites() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_quirks(Quirks::cosmac_vip());
+
+    let program = vec![
+        0x60, 0x3C, // LD V0, $3C
+        0x61, 0x1E, // LD V1, $1E
+        0x62, 0x00, // LD V2, $00
+        0xF2, 0x29, // LD F, V2
+        0xD0, 0x15, // DRW V0, V1, 5
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pixel(60, 30), (255, 255, 255));
+    assert_eq!(vm.get_pixel(60, 0), (0, 0, 0));
+}
+
+#[test]
+fn test_wrap_sprites() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x3C, // LD V0, $3C
+        0x61, 0x1E, // LD V1, $1E
+        0x62, 0x00, // LD V2, $00
+        0xF2, 0x29, // LD F, V2
+        0xD0, 0x15, // DRW V0, V1, 5
+        0xFF, 0xFF  // stop
+    ];
+
+    run(&mut vm, program, false);
+
+    assert_eq!(vm.get_pixel(60, 30), (255, 255, 255));
+    assert_eq!(vm.get_pixel(60, 0), (255, 255, 255));
+}
+
+#[test]
+fn test_sound_timer() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x02, // LD V0, $02
+        0xF0, 0x18, // LD ST, V0
+        0x12, 0x04  // JP $204
+    ];
+
+    vm.load_memory(program).unwrap();
+    vm.step().unwrap();
+    vm.step().unwrap();
+
+    assert!(vm.is_sound_active());
+    assert_eq!(vm.get_st(), 2);
+
+    // both timers count down at 60Hz
+    let start = Instant::now();
+    while vm.is_sound_active() {
+        vm.update().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500), "sound timer did not count down");
+    }
+
+    assert_eq!(vm.get_st(), 0);
+}
+
+#[test]
+fn test_tick_timers() {
+    let mut vm = Chip8::new(0.00001);
+
+    let program = vec![
+        0x60, 0x02, // LD V0, $02
+        0xF0, 0x15, // LD DT, V0
+        0xF0, 0x18  // LD ST, V0
+    ];
+
+    vm.load_memory(program).unwrap();
+    for _ in 0..3 {
+        vm.step().unwrap();
+    }
+
+    vm.tick_timers();
+    assert_eq!((vm.get_dt(), vm.get_st()), (1, 1));
+
+    // timers stop at zero
+    vm.tick_timers();
+    vm.tick_timers();
+    assert_eq!((vm.get_dt(), vm.get_st()), (0, 0));
+}
+
+#[test]
+fn test_timers_tick_every_frame() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_instructions_per_frame(10);
+
+    let program = vec![
+        0x60, 0x03, // LD V0, $03
+        0xF0, 0x15, // LD DT, V0
+        0x12, 0x04  // JP $204
+    ];
+
+    vm.load_memory(program).unwrap();
+
+    vm.run_for(9).unwrap();
+    assert_eq!(vm.get_dt(), 3);
+    assert_eq!(vm.get_frames(), 0);
+
+    // the tenth cycle ends the frame
+    vm.run_for(1).unwrap();
+    assert_eq!(vm.get_dt(), 2);
+    assert_eq!(vm.get_frames(), 1);
+
+    vm.run_for(5).unwrap();
+    vm.run_frame().unwrap();
+    assert_eq!(vm.get_dt(), 1);
+    assert_eq!(vm.get_cycles(), 20);
+
+    vm.run_frame().unwrap();
+    vm.run_frame().unwrap();
+    assert_eq!(vm.get_dt(), 0);
+    assert_eq!(vm.get_frames(), 4);
+}
+
+#[test]
+fn test_deterministic_runs() {
+    let program = vec![
+        0x60, 0x00, // LD V0, $00
+        0x70, 0x01, // ADD V0, $01
+        0xF0, 0x15, // LD DT, V0
+        0xF1, 0x07, // LD V1, DT
+        0x12, 0x02  // JP $202
+    ];
+
+    let run = || {
+        let mut vm = Chip8::new(0.00001);
+        vm.set_instructions_per_frame(7);
+        vm.load_memory(program.clone()).unwrap();
+
+        let mut trace = Vec::new();
+        for _ in 0..50 {
+            vm.run_frame().unwrap();
+            trace.push((vm.get_register(0), vm.get_register(1), vm.get_dt()));
+        }
+
+        trace
+    };
+
+    assert_eq!(run(), run());
+}
+
+fn random_values(vm: &mut Chip8, count: usize) -> Vec<u8> {
+    let program = vec![
+        0xC0, 0xFF, // RND V0, $FF
+        0x12, 0x00  // JP $200
+    ];
+
+    vm.load_memory(program).unwrap();
+
+    (0..count).map(|_| {
+        vm.run_for(2).unwrap();
+        vm.get_register(0)
+    }).collect()
+}
+
+#[test]
+fn test_seeded_random() {
+    let a = random_values(&mut Chip8::with_seed(0.00001, 1234), 32);
+    let b = random_values(&mut Chip8::with_seed(0.00001, 1234), 32);
+    let c = random_values(&mut Chip8::with_seed(0.00001, 4321), 32);
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
+
+struct Counter(u8);
+
+impl RandomSource for Counter {
+    fn next_byte(&mut self) -> u8 {
+        self.0 += 1;
+        self.0
+    }
+}
+
+#[test]
+fn test_random_source() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_random_source(Box::new(Counter(0)));
+
+    assert_eq!(random_values(&mut vm, 3), vec![1, 2, 3]);
+}
+
+fn fault(vm: &mut Chip8, program: Vec<u8>) -> Chip8Error {
+    vm.load_memory(program).unwrap();
+
+    loop {
+        if let Err(e) = vm.step() {
+            return e;
+        }
+    }
+}
+
+#[test]
+fn test_invalid_opcode_error() {
+    let mut vm = Chip8::new(0.00001);
+
+    let error = fault(&mut vm, vec![0x60, 0x01, 0xE0, 0x00]);
+    assert_eq!(error, Chip8Error { pc: 0x202, opcode: 0xE000, kind: ErrorKind::InvalidOpcode });
+    assert_eq!(error.to_string(), "0202: Invalid opcode E000");
+}
+
+#[test]
+fn test_stack_errors() {
+    let mut vm = Chip8::new(0.00001);
+    let error = fault(&mut vm, vec![0x22, 0x00]); // CALL $200
+    assert_eq!(error, Chip8Error { pc: 0x200, opcode: 0x2200, kind: ErrorKind::StackOverflow });
+    assert_eq!(vm.get_sp(), 16);
+
+    let mut vm = Chip8::new(0.00001);
+    let error = fault(&mut vm, vec![0x00, 0xEE]); // RET
+    assert_eq!(error.kind, ErrorKind::StackUnderflow);
+}
+
+#[test]
+fn test_address_out_of_range() {
+    let programs = vec![
+        vec![0xAF, 0xFE, 0xF0, 0x33],             // LD I, $FFE; LD B, V0
+        vec![0xAF, 0xFC, 0xF7, 0x55],             // LD I, $FFC; LD [I], V7
+        vec![0xAF, 0xFF, 0xF1, 0x65],             // LD I, $FFF; LD V1, [I]
+        vec![0xAF, 0xFE, 0xD0, 0x05],             // LD I, $FFE; DRW V0, V0, 5
+        vec![0xAF, 0xFF, 0x50, 0x12],             // LD I, $FFF; SAVE V0 - V1
+        vec![0xAF, 0xF8, 0xF0, 0x02]              // LD I, $FF8; AUDIO
+    ];
+
+    for program in programs {
+        let mut vm = Chip8::new(0.00001);
+        let error = fault(&mut vm, program);
+
+        assert_eq!(error.pc, 0x202);
+        assert_eq!(error.kind, ErrorKind::AddressOutOfRange(0x1000));
+    }
+
+    // running off the end of memory
+    let mut vm = Chip8::new(0.00001);
+    let error = fault(&mut vm, vec![0x1F, 0xFF]); // JP $FFF
+    assert_eq!(error, Chip8Error { pc: 0xFFF, opcode: 0, kind: ErrorKind::AddressOutOfRange(0x1000) });
+}
+
+#[test]
+fn test_fault_changes_nothing() {
+    let mut vm = Chip8::new(0.00001);
+
+    // LD V0, $AA; LD I, $FFC; LD [I], V7 writes four of its eight registers before the end
+    let error = fault(&mut vm, vec![0x60, 0xAA, 0xAF, 0xFC, 0xF7, 0x55]);
+    assert_eq!(error.pc, 0x204);
+
+    assert_eq!(vm.get_pc(), 0x204);
+    assert_eq!(vm.get_i(), 0xFFC);
+    for addr in 0xFFC..0x1000 {
+        assert_eq!(vm.get_memory(addr), 0);
+    }
+}
+
+#[test]
+fn test_invalid_key() {
+    let mut vm = Chip8::new(0.00001);
+
+    let error = fault(&mut vm, vec![0x60, 0x10, 0xE0, 0x9E]); // LD V0, $10; SKP V0
+    assert_eq!(error.kind, ErrorKind::InvalidKey(0x10));
+}
+
+#[test]
+fn test_rom_too_large() {
+    let mut vm = Chip8::new(0.00001);
+
+    let error = vm.load_memory(vec![0; 4096]).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::RomTooLarge { size: 4096, max: 3584 });
+    assert!(vm.load_memory(vec![0; 3584]).is_ok());
+}
@@ -0,0 +1,85 @@
+extern crate libchip8;
+
+use libchip8::*;
+
+fn rom() -> Vec<u8> {
+    vec![
+        0x60, 0x05, // LD V0, $05
+        0xE0, 0x9E, // SKP V0
+        0x12, 0x02, // JP $202
+        0xC1, 0xFF, // RND V1, $FF
+        0x72, 0x01, // ADD V2, $01
+        0xF1, 0x29, // LD F, V1
+        0xD2, 0x35, // DRW V2, V3, 5
+        0x12, 0x02  // JP $202
+    ]
+}
+
+fn create_vm<'a>(rom: &[u8]) -> Chip8<'a> {
+    let mut vm = Chip8::with_seed(0.00001, 7);
+    vm.set_instructions_per_frame(20);
+    vm.load_memory(rom.to_vec()).unwrap();
+
+    vm
+}
+
+#[test]
+fn test_record_replay() {
+    let rom = rom();
+
+    let mut vm = create_vm(&rom);
+    vm.start_recording(&rom);
+
+    for frame in 0..30 {
+        // tap key 5 every few frames, in the middle of a frame
+        vm.run_for(7).unwrap();
+        vm.key(5, frame % 4 == 1);
+        vm.run_frame().unwrap();
+    }
+
+    let movie = vm.stop_recording().unwrap();
+    assert_eq!(movie.events.len(), 15);
+    assert_eq!(movie.events[0], KeyEvent { frame: 1, cycle: 27, key: 5, pressed: true });
+
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+
+    // replay on a machine with a different seed and speed, the movie restores them
+    let mut replay = Chip8::new(0.00001);
+    replay.load_memory(rom.clone()).unwrap();
+    movie.replay(&mut replay, &rom).unwrap();
+
+    assert!(replay.is_replaying());
+    replay.key(5, true); // ignored while replaying
+
+    for _ in 0..30 {
+        replay.run_frame().unwrap();
+    }
+
+    assert!(!replay.is_replaying());
+    assert_eq!(replay.save_state(), vm.save_state());
+}
+
+#[test]
+fn test_replay_wrong_rom() {
+    let rom = rom();
+
+    let mut vm = create_vm(&rom);
+    vm.start_recording(&rom);
+    let movie = vm.stop_recording().unwrap();
+
+    let mut other = Chip8::new(0.00001);
+    assert_eq!(movie.replay(&mut other, &[0x00, 0xE0]), Err(MovieError::RomMismatch));
+}
+
+#[test]
+fn test_movie_errors() {
+    assert_eq!(Movie::from_bytes(b"nope"), Err(MovieError::BadMagic));
+
+    let rom = rom();
+    let mut vm = create_vm(&rom);
+    vm.start_recording(&rom);
+    vm.key(1, true);
+
+    let data = vm.stop_recording().unwrap().to_bytes();
+    assert_eq!(Movie::from_bytes(&data[..(data.len() - 1)]), Err(MovieError::Truncated));
+}
@@ -0,0 +1,80 @@
+extern crate libchip8;
+
+use libchip8::*;
+
+fn program() -> Vec<u8> {
+    vec![
+        0x00, 0xE0, // CLS
+        0x70, 0x01, // ADD V0, $01
+        0xC1, 0xFF, // RND V1, $FF
+        0xF1, 0x29, // LD F, V1
+        0xD0, 0x05, // DRW V0, V0, 5
+        0x12, 0x00  // JP $200
+    ]
+}
+
+fn create_vm<'a>() -> Chip8<'a> {
+    let mut vm = Chip8::with_seed(0.00001, 3);
+    vm.set_instructions_per_frame(10);
+    vm.load_memory(program()).unwrap();
+
+    vm
+}
+
+#[test]
+fn test_rewind() {
+    let mut vm = create_vm();
+    let mut rewind = RewindBuffer::new(100);
+
+    let mut states = Vec::new();
+
+    for _ in 0..10 {
+        rewind.push(&vm);
+        states.push(vm.save_state());
+        vm.run_frame().unwrap();
+    }
+
+    assert_eq!(rewind.len(), 10);
+
+    // the deltas are much smaller than the states
+    assert!(rewind.get_size() < 2 * states[0].len());
+
+    while let Some(state) = states.pop() {
+        assert!(rewind.pop(&mut vm));
+        assert_eq!(vm.save_state(), state);
+    }
+
+    assert!(rewind.is_empty());
+    assert!(!rewind.pop(&mut vm));
+
+    // running again from the start repeats the run, random numbers included
+    let mut other = create_vm();
+    vm.run_for(35).unwrap();
+    other.run_for(35).unwrap();
+    assert_eq!(vm.save_state(), other.save_state());
+}
+
+#[test]
+fn test_rewind_depth() {
+    let mut vm = create_vm();
+    let mut rewind = RewindBuffer::new(3);
+
+    for _ in 0..5 {
+        rewind.push(&vm);
+        vm.run_frame().unwrap();
+    }
+
+    assert_eq!(rewind.len(), 3);
+
+    for _ in 0..3 {
+        assert!(rewind.pop(&mut vm));
+    }
+
+    // the two oldest snapshots were dropped
+    assert_eq!(vm.get_frames(), 2);
+    assert!(rewind.is_empty());
+
+    let mut disabled = RewindBuffer::new(0);
+    disabled.push(&vm);
+    assert!(disabled.is_empty());
+}
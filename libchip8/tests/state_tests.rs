@@ -0,0 +1,190 @@
+extern crate libchip8;
+
+use libchip8::*;
+
+fn program() -> Vec<u8> {
+    vec![
+        0x60, 0x05, // LD V0, $05
+        0x22, 0x08, // CALL $208
+        0x12, 0x04, // JP $204
+        0x00, 0x00,
+        0x70, 0x01, // ADD V0, $01
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x05, // DRW V0, V0, 5
+        0xF0, 0x15, // LD DT, V0
+        0x00, 0xEE  // RET
+    ]
+}
+
+#[test]
+fn test_save_load_state() {
+    let mut vm = Chip8::new(0.00001);
+    vm.load_memory(program()).unwrap();
+    vm.key(3, true);
+
+    for _ in 0..4 {
+        vm.step().unwrap();
+    }
+
+    let state = vm.save_state();
+
+    let mut restored = Chip8::new(0.00001);
+    restored.load_state(&state).unwrap();
+
+    assert_eq!(restored.get_pc(), vm.get_pc());
+    assert_eq!(restored.get_sp(), 1);
+    assert_eq!(restored.get_stack(0), 0x204);
+    assert_eq!(restored.get_register(0), 6);
+    assert_eq!(restored.get_i(), vm.get_i());
+    assert_eq!(restored.get_display_memory(), vm.get_display_memory());
+    assert_eq!(restored.save_state(), state);
+
+    // both machines continue identically
+    for _ in 0..4 {
+        vm.step().unwrap();
+        restored.step().unwrap();
+    }
+
+    assert_eq!(restored.save_state(), vm.save_state());
+    assert_eq!(restored.get_dt(), 6);
+}
+
+#[test]
+fn test_load_state_bad_magic() {
+    let mut vm = Chip8::new(0.00001);
+
+    assert_eq!(vm.load_state(b"not a state"), Err(StateError::BadMagic));
+}
+
+#[test]
+fn test_load_state_unsupported_version() {
+    let mut vm = Chip8::new(0.00001);
+
+    let mut state = vm.save_state();
+    state[4] = 0xFF;
+
+    assert_eq!(vm.load_state(&state), Err(StateError::UnsupportedVersion(0xFF)));
+}
+
+#[test]
+fn test_load_state_truncated() {
+    let mut vm = Chip8::new(0.00001);
+    vm.load_memory(program()).unwrap();
+
+    let state = vm.save_state();
+    let mut other = Chip8::new(0.00001);
+
+    assert_eq!(other.load_state(&state[..(state.len() - 1)]), Err(StateError::Truncated));
+    // a failed load leaves the machine untouched
+    assert_eq!(other.get_display_memory(), Chip8::new(0.00001).get_display_memory());
+    assert_eq!(other.get_pc(), 0x200);
+}
+
+#[test]
+fn test_load_state_memory_size_mismatch() {
+    let vm = Chip8::with_memory_size(0.00001, XO_MEMORY_SIZE);
+    let mut other = Chip8::new(0.00001);
+
+    let expected = StateError::MemorySizeMismatch {
+        expected: MEMORY_SIZE,
+        found: XO_MEMORY_SIZE
+    };
+
+    assert_eq!(other.load_state(&vm.save_state()), Err(expected));
+}
+
+#[test]
+fn test_load_state_invalid_pixel() {
+    let mut vm = Chip8::new(0.00001);
+    vm.load_memory(program()).unwrap();
+
+    let mut state = vm.save_state();
+    // the display section's payload follows its tag and length, the pixels follow the planes
+    let display = state.windows(4).position(|tag| tag == b"DISP").unwrap() + 8;
+    state[display + 2 + 10] = 4;
+
+    let mut other = Chip8::new(0.00001);
+
+    assert_eq!(other.load_state(&state), Err(StateError::InvalidSection("DISP".to_string())));
+    assert_eq!(other.get_display_memory(), Chip8::new(0.00001).get_display_memory());
+}
+
+#[test]
+fn test_save_load_xochip_rpl_flags() {
+    let mut vm = Chip8::with_memory_size(0.00001, XO_MEMORY_SIZE);
+    let flags: Vec<u8> = (1..17).collect();
+    vm.set_rpl_flags(&flags);
+
+    let mut restored = Chip8::with_memory_size(0.00001, XO_MEMORY_SIZE);
+    restored.load_state(&vm.save_state()).unwrap();
+
+    assert_eq!(restored.get_rpl_flags(), flags.as_slice());
+}
+
+#[test]
+fn test_save_load_clock() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_instructions_per_frame(4);
+    vm.load_memory(program()).unwrap();
+    vm.run_for(6).unwrap();
+
+    let mut restored = Chip8::new(0.00001);
+    restored.set_instructions_per_frame(4);
+    restored.load_state(&vm.save_state()).unwrap();
+
+    assert_eq!(restored.get_cycles(), 6);
+    assert_eq!(restored.get_frames(), 1);
+
+    // the frame ends after the same number of cycles
+    restored.run_frame().unwrap();
+    assert_eq!(restored.get_cycles(), 8);
+}
+
+#[test]
+fn test_save_load_random_state() {
+    let program = vec![
+        0xC0, 0xFF, // RND V0, $FF
+        0x12, 0x00  // JP $200
+    ];
+
+    let mut vm = Chip8::with_seed(0.00001, 99);
+    vm.load_memory(program).unwrap();
+    vm.run_for(10).unwrap();
+
+    let mut restored = Chip8::with_seed(0.00001, 1);
+    restored.load_state(&vm.save_state()).unwrap();
+
+    for _ in 0..10 {
+        vm.run_for(2).unwrap();
+        restored.run_for(2).unwrap();
+        assert_eq!(restored.get_register(0), vm.get_register(0));
+    }
+}
+
+#[test]
+fn test_save_load_key_wait() {
+    let mut vm = Chip8::new(0.00001);
+    vm.load_memory(vec![0xF3, 0x0A]).unwrap(); // LD V3, K
+    vm.step().unwrap();
+    vm.key(9, true);
+
+    let mut restored = Chip8::new(0.00001);
+    restored.load_state(&vm.save_state()).unwrap();
+    assert!(restored.is_waiting_for_key());
+
+    restored.key(9, false);
+    assert!(!restored.is_waiting_for_key());
+    assert_eq!(restored.get_register(3), 9);
+}
+
+#[test]
+fn test_save_load_display_wait() {
+    let mut vm = Chip8::new(0.00001);
+    vm.set_quirks(Quirks::cosmac_vip());
+    vm.load_memory(vec![0xD0, 0x01]).unwrap(); // DRW V0, V0, 1
+    vm.step().unwrap();
+
+    let mut restored = Chip8::new(0.00001);
+    restored.load_state(&vm.save_state()).unwrap();
+    assert!(restored.is_waiting_for_display());
+}
@@ -0,0 +1,69 @@
+extern crate libchip8;
+
+use libchip8::*;
+
+use std::cell::RefCell;
+
+#[test]
+fn test_tracer() {
+    let entries = RefCell::new(Vec::new());
+
+    {
+        let mut vm = Chip8::new(0.00001);
+        vm.set_tracer(Box::new(|entry: &TraceEntry| entries.borrow_mut().push(*entry)));
+        vm.load_memory(vec![
+            0x60, 0x05,             // LD V0, $05
+            0xF0, 0x00, 0x12, 0x34, // LD I, LONG $1234
+            0x00, 0xFD              // EXIT
+        ]).unwrap();
+
+        for _ in 0..5 {
+            vm.step().unwrap();
+        }
+    }
+
+    let entries = entries.into_inner();
+
+    // the halted machine executes nothing
+    assert_eq!(entries.len(), 3);
+
+    assert_eq!(entries[0].cycle, 0);
+    assert_eq!(entries[0].pc, 0x200);
+    assert_eq!(entries[0].instruction, Instruction::LDVXB(0, 5));
+    assert_eq!(entries[0].before.v[0], 0);
+    assert_eq!(entries[0].after.v[0], 5);
+
+    assert_eq!(entries[1].opcode, 0xF000);
+    assert_eq!(entries[1].operand, 0x1234);
+    assert_eq!(entries[1].after.i, 0x1234);
+
+    assert_eq!(
+        entries[1].to_text(),
+        "0202 F000 LD I, LONG 0x1234    I=0000 V=05000000000000000000000000000000 -> I=1234"
+    );
+    assert_eq!(entries[2].to_text(), "0206 00FD EXIT                 I=1234 V=05000000000000000000000000000000");
+}
+
+#[test]
+fn test_trace_bytes() {
+    let mut before = Registers { v: [0; 16], i: 0x300 };
+    before.v[3] = 0x42;
+
+    let mut after = before;
+    after.v[0xF] = 1;
+
+    let entry = TraceEntry {
+        cycle: 123456,
+        pc: 0x2A0,
+        opcode: 0xD015,
+        operand: 0,
+        instruction: Instruction::DRAW(0, 1, 5),
+        before: before,
+        after: after
+    };
+
+    let data = entry.to_bytes();
+    assert_eq!(data.len(), TRACE_ENTRY_SIZE);
+    assert_eq!(TraceEntry::from_bytes(&data), Some(entry));
+    assert_eq!(TraceEntry::from_bytes(&data[1..]), None);
+}
@@ -0,0 +1,65 @@
+use sdl2::AudioSubsystem;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+/// Square wave generator fed to the SDL audio device
+pub struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            *x = if self.phase <= 0.5 { self.volume } else { -self.volume };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+/// Plays a tone while the Chip8 sound timer is active
+pub struct Beeper {
+    device: AudioDevice<SquareWave>,
+    active: bool
+}
+
+impl Beeper {
+    pub fn new(audio_subsystem: &AudioSubsystem, pitch: f32, volume: f32) -> Result<Self, String> {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(1),
+            samples: None
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            SquareWave {
+                phase_inc: pitch / spec.freq as f32,
+                phase: 0.0,
+                volume: volume
+            }
+        })?;
+
+        Ok(Beeper {
+            device: device,
+            active: false
+        })
+    }
+
+    /// Start or stop the tone, does nothing if it is already in the requested state
+    pub fn set_active(&mut self, active: bool) {
+        if active == self.active {
+            return;
+        }
+
+        if active {
+            self.device.resume();
+        }
+        else {
+            self.device.pause();
+        }
+
+        self.active = active;
+    }
+}
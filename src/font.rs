@@ -0,0 +1,105 @@
+//! Text drawn with the CHIP-8 font
+//!
+//! The CHIP-8 font only has the hex digits, the other letters and some punctuation are drawn in
+//! the same style: 4 pixels wide and 5 rows high, one row per byte in the high nybble.
+
+use libchip8::FONT;
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 5;
+
+const UNKNOWN: [u8; GLYPH_HEIGHT] = [0xE0, 0x10, 0x60, 0x00, 0x40];
+
+/// Rows of the glyph for `c`, letters are drawn upper case and unknown characters as `?`
+pub fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+
+    if let Some(digit) = c.to_digit(16) {
+        let start = digit as usize * GLYPH_HEIGHT;
+        let mut rows = [0; GLYPH_HEIGHT];
+        rows.copy_from_slice(&FONT[start..(start + GLYPH_HEIGHT)]);
+        return rows;
+    }
+
+    match c {
+        'G' => [0xF0, 0x80, 0xB0, 0x90, 0xF0],
+        'H' => [0x90, 0x90, 0xF0, 0x90, 0x90],
+        'I' => [0xE0, 0x40, 0x40, 0x40, 0xE0],
+        'J' => [0x70, 0x20, 0x20, 0xA0, 0xE0],
+        'K' => [0x90, 0xA0, 0xC0, 0xA0, 0x90],
+        'L' => [0x80, 0x80, 0x80, 0x80, 0xF0],
+        'M' => [0x90, 0xF0, 0xF0, 0x90, 0x90],
+        'N' => [0x90, 0xD0, 0xB0, 0x90, 0x90],
+        'O' => [0x60, 0x90, 0x90, 0x90, 0x60],
+        'P' => [0xF0, 0x90, 0xF0, 0x80, 0x80],
+        'Q' => [0x60, 0x90, 0x90, 0xB0, 0x70],
+        'R' => [0xE0, 0x90, 0xE0, 0xA0, 0x90],
+        'S' => [0xF0, 0x80, 0xF0, 0x10, 0xF0],
+        'T' => [0xF0, 0x40, 0x40, 0x40, 0x40],
+        'U' => [0x90, 0x90, 0x90, 0x90, 0xF0],
+        'V' => [0x90, 0x90, 0x90, 0x90, 0x60],
+        'W' => [0x90, 0x90, 0xF0, 0xF0, 0x90],
+        'X' => [0x90, 0x90, 0x60, 0x90, 0x90],
+        'Y' => [0xA0, 0xA0, 0x40, 0x40, 0x40],
+        'Z' => [0xF0, 0x10, 0x60, 0x80, 0xF0],
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x40],
+        ',' => [0x00, 0x00, 0x00, 0x40, 0x80],
+        ':' => [0x00, 0x40, 0x00, 0x40, 0x00],
+        '-' => [0x00, 0x00, 0xE0, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0xF0],
+        '+' => [0x00, 0x40, 0xE0, 0x40, 0x00],
+        '/' => [0x10, 0x20, 0x20, 0x40, 0x80],
+        '(' => [0x20, 0x40, 0x40, 0x40, 0x20],
+        ')' => [0x40, 0x20, 0x20, 0x20, 0x40],
+        '[' => [0x60, 0x40, 0x40, 0x40, 0x60],
+        ']' => [0x60, 0x20, 0x20, 0x20, 0x60],
+        '\'' => [0x40, 0x40, 0x00, 0x00, 0x00],
+        '!' => [0x40, 0x40, 0x40, 0x00, 0x40],
+        '&' => [0x40, 0xA0, 0x40, 0xA0, 0x50],
+        '#' => [0x50, 0xF0, 0x50, 0xF0, 0x50],
+        '>' => [0x80, 0x40, 0x20, 0x40, 0x80],
+        _ => UNKNOWN
+    }
+}
+
+/// Draw `text` with its top left corner at `x`, `y` into `pixels`, an image `width` pixels wide,
+/// setting lit pixels to `value`. Characters advance by a glyph and a column of space
+pub fn draw_text(pixels: &mut [u8], width: usize, x: usize, y: usize, text: &str, value: u8) {
+    let height = pixels.len() / width;
+
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i * (GLYPH_WIDTH + 1);
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                let (px, py) = (left + column, y + row);
+
+                if bits & (0x80 >> column) != 0 && px < width && py < height {
+                    pixels[py * width + px] = value;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph() {
+        assert_eq!(glyph('0'), [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(glyph('f'), glyph('F'));
+        assert_eq!(glyph('~'), UNKNOWN);
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut pixels = [0; 12 * 6];
+        draw_text(&mut pixels, 12, 1, 1, "1L", 1);
+
+        // the top rows of 1 (..1.) and L (1...), a column apart
+        assert_eq!(&pixels[12..24], &[0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0]);
+    }
+}
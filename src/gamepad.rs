@@ -0,0 +1,38 @@
+//! Game controllers, opened as they are connected
+
+use sdl2::GameControllerSubsystem;
+use sdl2::controller::GameController;
+
+use std::collections::HashMap;
+
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    controllers: HashMap<i32, GameController> // open controllers by instance id
+}
+
+impl Gamepads {
+    pub fn new(subsystem: GameControllerSubsystem) -> Self {
+        Gamepads {
+            subsystem: subsystem,
+            controllers: HashMap::new()
+        }
+    }
+
+    /// Open a connected controller. SDL reports controllers that are already connected at startup too
+    pub fn add(&mut self, joystick_index: i32) {
+        match self.subsystem.open(joystick_index as u32) {
+            Ok(controller) => {
+                println!("Controller connected: {}", controller.name());
+                self.controllers.insert(controller.instance_id(), controller);
+            },
+            Err(e) => println!("Failed to open controller {}: {}", joystick_index, e)
+        }
+    }
+
+    /// Close a disconnected controller
+    pub fn remove(&mut self, instance_id: i32) {
+        if let Some(controller) = self.controllers.remove(&instance_id) {
+            println!("Controller disconnected: {}", controller.name());
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Recording the display to animated GIFs
+//!
+//! The GIF has the size of the high resolution display, low resolution frames are doubled. Pixel
+//! values are used as indices into a palette of four colours, and a frame is only written when the
+//! display changes, showing for as long as it stayed on screen.
+
+use gif::{Encoder, Frame, Repeat, SetParameter};
+use libchip8::Chip8;
+use palette::Palette;
+use screenshot;
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+
+/// Shortest frame delay in hundredths of a second, viewers slow shorter ones down
+const MIN_DELAY: u64 = 2;
+
+pub struct GifRecorder {
+    encoder: Encoder<BufWriter<File>>,
+    scale: usize,
+    image: Option<Vec<u8>>, // frame on screen, not written yet
+    frames: u64,            // 60Hz frames recorded
+    written: u64            // hundredths of a second written
+}
+
+impl GifRecorder {
+    /// Start recording to `file`, in the colours of `palette` and enlarged `scale` times
+    pub fn create(file: &str, palette: &Palette, scale: usize) -> Result<Self, Box<Error>> {
+        let scale = scale.max(1);
+
+        let mut colours = Vec::new();
+        for value in 0..4 {
+            let (r, g, b) = palette.get_colour(value);
+            colours.extend_from_slice(&[r, g, b]);
+        }
+
+        let writer = BufWriter::new(File::create(file)?);
+        let mut encoder = Encoder::new(writer, (WIDTH * scale) as u16, (HEIGHT * scale) as u16, &colours)?;
+        encoder.set(Repeat::Infinite)?;
+
+        Ok(GifRecorder {
+            encoder: encoder,
+            scale: scale,
+            image: None,
+            frames: 0,
+            written: 0
+        })
+    }
+
+    /// Record the display for one 60Hz frame
+    pub fn add_frame(&mut self, vm: &Chip8) -> Result<(), Box<Error>> {
+        let width = vm.get_display_width();
+        let height = vm.get_display_height();
+
+        let mut image = Vec::with_capacity(WIDTH * HEIGHT);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                image.push(vm.get_pixel_value(x * width / WIDTH, y * height / HEIGHT));
+            }
+        }
+
+        if self.image.as_ref() != Some(&image) {
+            self.write_image(false)?;
+            self.image = Some(image);
+        }
+
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    /// Write the last frame and end the file
+    pub fn finish(mut self) -> Result<(), Box<Error>> {
+        self.write_image(true)
+    }
+
+    /// Write the frame on screen. Unless `last` is set, a frame shown too briefly is dropped and
+    /// its time given to the next one
+    fn write_image(&mut self, last: bool) -> Result<(), Box<Error>> {
+        let end = self.frames * 100 / 60;
+        let delay = end.saturating_sub(self.written);
+
+        if delay < MIN_DELAY && !last {
+            return Ok(());
+        }
+
+        if let Some(ref image) = self.image {
+            let frame = Frame {
+                width: (WIDTH * self.scale) as u16,
+                height: (HEIGHT * self.scale) as u16,
+                delay: delay.max(MIN_DELAY).min(u16::max_value() as u64) as u16,
+                buffer: Cow::Owned(screenshot::enlarge(image, WIDTH, 1, self.scale)),
+                ..Frame::default()
+            };
+
+            self.encoder.write_frame(&frame)?;
+            self.written = end;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Running roms without a window or audio, for automated testing
+
+use libchip8::Chip8;
+use quartz::options::ProgramOptions;
+use gif_recorder::GifRecorder;
+use palette::Palette;
+use screenshot;
+use save_recording;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+/// Exit status when the machine faulted, e.g. on an invalid instruction
+const EXIT_FAULT: i32 = 2;
+
+/// A scripted key press or release
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct KeyEvent {
+    frame: u64,
+    key: u8,
+    pressed: bool
+}
+
+/// Run the rom for the requested number of cycles or frames, then write the screenshot and
+/// registers. Returns the exit status
+pub fn run(vm: Chip8, options: &ProgramOptions) -> i32 {
+    let script = match options.flag_keys {
+        Some(ref file) => match read_key_script(file) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                return 1;
+            }
+        },
+        None => Vec::new()
+    };
+
+    let mut gif = match options.flag_gif {
+        Some(ref file) => match GifRecorder::create(file, &Palette::new(), options.flag_screenshot_scale) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!("Failed to record {}: {}", file, e);
+                return 1;
+            }
+        },
+        None => None
+    };
+
+    let max_cycles = options.flag_cycles.unwrap_or(u64::max_value());
+    let max_frames = options.flag_frames.unwrap_or(u64::max_value());
+
+    let mut frame = 0;
+    let mut cycles = 0;
+
+    let mut vm = vm;
+    let mut result = Ok(());
+
+    'running: while frame < max_frames {
+        for event in script.iter().filter(|event| event.frame == frame) {
+            vm.key(event.key, event.pressed);
+        }
+
+        // run one frame of the machine's clock, the timers tick at its end
+        let start_frame = vm.get_frames();
+
+        while vm.get_frames() == start_frame {
+            if cycles >= max_cycles || vm.is_halted() {
+                break 'running;
+            }
+
+            if let Err(e) = vm.step() {
+                result = Err(e);
+                break 'running;
+            }
+
+            cycles += 1;
+        }
+
+        if let Some(ref mut recorder) = gif {
+            if let Err(e) = recorder.add_frame(&vm) {
+                eprintln!("Failed to write {}: {}", options.flag_gif.as_ref().unwrap(), e);
+                return 1;
+            }
+        }
+
+        frame += 1;
+    }
+
+    if let Some(recorder) = gif {
+        if let Err(e) = recorder.finish() {
+            eprintln!("Failed to write {}: {}", options.flag_gif.as_ref().unwrap(), e);
+            return 1;
+        }
+    }
+
+    if let Err(e) = save_recording(&mut vm, options) {
+        eprintln!("{}", e);
+        return 1;
+    }
+
+    if let Some(ref file) = options.flag_screenshot {
+        if let Err(e) = screenshot::save(&vm, file, options.flag_screenshot_scale) {
+            eprintln!("Failed to write {}: {}", file, e);
+            return 1;
+        }
+    }
+
+    if let Some(ref file) = options.flag_registers {
+        let json = registers_json(&vm, cycles, frame);
+
+        let written = if file == "-" {
+            println!("{}", json);
+            Ok(())
+        }
+        else {
+            File::create(file).and_then(|mut f| writeln!(f, "{}", json))
+        };
+
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", file, e);
+            return 1;
+        }
+    }
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_FAULT
+        }
+    }
+}
+
+/// Read a key script. Each line is `<frame> <key> <down|up>` with the key in hex, `#` starts a comment
+fn read_key_script(file: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut script = String::new();
+    File::open(file).and_then(|mut f| f.read_to_string(&mut script)).map_err(|e| e.to_string())?;
+
+    let mut events = Vec::new();
+
+    for (index, line) in script.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        let event = match parts[..] {
+            [frame, key, action] => match (frame.parse(), u8::from_str_radix(key, 16), action) {
+                (Ok(frame), Ok(key), "down") if key < 16 => Some(KeyEvent { frame: frame, key: key, pressed: true }),
+                (Ok(frame), Ok(key), "up") if key < 16 => Some(KeyEvent { frame: frame, key: key, pressed: false }),
+                _ => None
+            },
+            _ => None
+        };
+
+        match event {
+            Some(event) => events.push(event),
+            None => return Err(format!("line {}: expected '<frame> <key> <down|up>'", index + 1))
+        }
+    }
+
+    Ok(events)
+}
+
+fn registers_json(vm: &Chip8, cycles: u64, frames: u64) -> String {
+    let v: Vec<String> = (0..16).map(|x| vm.get_register(x).to_string()).collect();
+    let stack: Vec<String> = (0..vm.get_sp() as usize).map(|i| vm.get_stack(i).to_string()).collect();
+
+    format!(
+        "{{\"pc\": {}, \"i\": {}, \"sp\": {}, \"dt\": {}, \"st\": {}, \"v\": [{}], \"stack\": [{}], \"halted\": {}, \"cycles\": {}, \"frames\": {}}}",
+        vm.get_pc(), vm.get_i(), vm.get_sp(), vm.get_dt(), vm.get_st(), v.join(", "), stack.join(", "),
+        vm.is_halted(), cycles, frames
+    )
+}
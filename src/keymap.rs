@@ -0,0 +1,140 @@
+//! Mapping of keyboard keys and gamepad buttons to the CHIP-8 keypad
+//!
+//! A keymap file is TOML. Each table maps CHIP-8 keys, in hex, to SDL key or button names, and
+//! replaces the default bindings of the keys it lists. Tables under `roms` apply to a single rom,
+//! selected by its file name or SHA-1.
+//!
+//! ```toml
+//! [keyboard]
+//! 5 = ["W", "Up"]
+//!
+//! [gamepad]
+//! 5 = ["dpup"]
+//!
+//! [roms."PONG".keyboard]
+//! 1 = ["W"]
+//! 4 = ["S"]
+//! ```
+
+use sdl2::controller::Button;
+use sdl2::keyboard::Scancode;
+use toml;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+/// The conventional layout, the left four columns of the keyboard
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  ->  Q W E R
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+const DEFAULT_KEYBOARD: [(Scancode, u8); 16] = [
+    (Scancode::Num1, 0x1), (Scancode::Num2, 0x2), (Scancode::Num3, 0x3), (Scancode::Num4, 0xC),
+    (Scancode::Q, 0x4),    (Scancode::W, 0x5),    (Scancode::E, 0x6),    (Scancode::R, 0xD),
+    (Scancode::A, 0x7),    (Scancode::S, 0x8),    (Scancode::D, 0x9),    (Scancode::F, 0xE),
+    (Scancode::Z, 0xA),    (Scancode::X, 0x0),    (Scancode::C, 0xB),    (Scancode::V, 0xF)
+];
+
+/// The D-pad moves with the keys most games use for directions, A is the usual action key
+const DEFAULT_GAMEPAD: [(Button, u8); 6] = [
+    (Button::DPadUp, 0x2), (Button::DPadLeft, 0x4), (Button::DPadRight, 0x6), (Button::DPadDown, 0x8),
+    (Button::A, 0x5),      (Button::B, 0x0)
+];
+
+#[derive(Debug, Default, Deserialize)]
+struct Bindings {
+    #[serde(default)]
+    keyboard: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    gamepad: HashMap<String, Vec<String>>
+}
+
+#[derive(Debug, Deserialize)]
+struct KeymapFile {
+    #[serde(default)]
+    keyboard: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    gamepad: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    roms: HashMap<String, Bindings>
+}
+
+pub struct Keymap {
+    keyboard: HashMap<Scancode, u8>,
+    gamepad: HashMap<Button, u8>
+}
+
+impl Keymap {
+    /// The default bindings
+    pub fn new() -> Self {
+        Keymap {
+            keyboard: DEFAULT_KEYBOARD.iter().cloned().collect(),
+            gamepad: DEFAULT_GAMEPAD.iter().cloned().collect()
+        }
+    }
+
+    /// Apply the bindings of a keymap file, with the overrides for the rom named `rom_file` and
+    /// hashed `rom_hash`
+    pub fn load(&mut self, file: &str, rom_file: &str, rom_hash: &str) -> Result<(), String> {
+        let mut contents = String::new();
+        File::open(file).and_then(|mut f| f.read_to_string(&mut contents)).map_err(|e| e.to_string())?;
+
+        let config: KeymapFile = toml::from_str(&contents).map_err(|e| e.to_string())?;
+
+        self.apply(&config.keyboard, &config.gamepad)?;
+
+        let rom_name = Path::new(rom_file).file_name().map_or(rom_file.to_string(), |name| name.to_string_lossy().into_owned());
+
+        for (rom, bindings) in config.roms.iter() {
+            if *rom == rom_name || rom.to_lowercase() == rom_hash {
+                self.apply(&bindings.keyboard, &bindings.gamepad)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// CHIP-8 key bound to a keyboard key
+    pub fn get_key(&self, scancode: Scancode) -> Option<u8> {
+        self.keyboard.get(&scancode).cloned()
+    }
+
+    /// CHIP-8 key bound to a gamepad button
+    pub fn get_button(&self, button: Button) -> Option<u8> {
+        self.gamepad.get(&button).cloned()
+    }
+
+    /// Replace the bindings of the CHIP-8 keys listed in `keyboard` and `gamepad`
+    pub fn apply(&mut self, keyboard: &HashMap<String, Vec<String>>, gamepad: &HashMap<String, Vec<String>>) -> Result<(), String> {
+        bind(&mut self.keyboard, keyboard, Scancode::from_name, "key")?;
+        bind(&mut self.gamepad, gamepad, Button::from_string, "button")
+    }
+}
+
+/// Replace the bindings of the CHIP-8 keys in `bindings`
+fn bind<T, F>(map: &mut HashMap<T, u8>, bindings: &HashMap<String, Vec<String>>, parse: F, what: &str) -> Result<(), String>
+    where T: ::std::hash::Hash + Eq, F: Fn(&str) -> Option<T>
+{
+    for (key, names) in bindings.iter() {
+        let key = match u8::from_str_radix(key, 16) {
+            Ok(key) if key < 16 => key,
+            _ => return Err(format!("'{}' is not a CHIP-8 key, expected 0-F", key))
+        };
+
+        map.retain(|_, bound| *bound != key);
+
+        for name in names.iter() {
+            match parse(name) {
+                Some(input) => map.insert(input, key),
+                None => return Err(format!("Unknown {} '{}'", what, name))
+            };
+        }
+    }
+
+    Ok(())
+}
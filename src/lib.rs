@@ -10,18 +10,87 @@ pub mod options {
     Quartz
 
     Usage:
-      quartz <romfile> [--irate=<r>]
+      quartz <romfile> [--irate=<r>] [--quirks=<name>] [--pitch=<hz>] [--volume=<v>] [--ipf=<n>] [--seed=<n>] [--load-state=<file>] [--record=<file> | --replay=<file>] [--rewind-depth=<n>] [--rewind-interval=<n>] [--trace=<file>] [--trace-format=<fmt>] [--keymap=<file>] [--romdb=<file>] [--theme=<name>] [--palette=<colours>] [--fg=<colour>] [--bg=<colour>] [--phosphor=<p>] [--scale=<n>] [--vsync] [--screenshot=<file>] [--screenshot-scale=<n>] [--gif=<file>] [--debug]
+      quartz <romfile> --headless (--cycles=<n> | --frames=<n>) [--irate=<r>] [--ipf=<n>] [--seed=<n>] [--quirks=<name>] [--romdb=<file>] [--load-state=<file>] [--record=<file> | --replay=<file>] [--trace=<file>] [--trace-format=<fmt>] [--keys=<file>] [--screenshot=<file>] [--screenshot-scale=<n>] [--gif=<file>] [--registers=<file>]
+      quartz disasm <romfile>
+      quartz asm <source> <romfile>
       quartz (-h | --help)
 
+    Arguments:
+      <romfile>         Rom file, ZIP archive, rom in an archive as games.zip/PONG.ch8, directory to pick a rom from, or - for stdin
+
     Options:
-      --irate=<r>    Instruction rate [default: 0.0001]
-      -h --help      Show help.
+      --irate=<r>       Instruction rate, 0.0001 if neither it nor the instructions per frame are set
+      --ipf=<n>         Instructions per 60Hz frame, overrides the instruction rate
+      --seed=<n>        Seed for random numbers, runs with the same seed are reproducible
+      --quirks=<name>   Interpreter quirks: default, vip, chip48, schip, xochip
+      --pitch=<hz>      Buzzer pitch in Hz [default: 440]
+      --volume=<v>      Buzzer volume from 0.0 to 1.0 [default: 0.25]
+      --load-state=<file>  Restore a save state after loading the rom
+      --record=<file>   Record the key presses of the run to a movie file
+      --replay=<file>   Replay the key presses of a movie file recorded with the same rom
+      --rewind-depth=<n>     Number of snapshots kept for rewinding with Backspace, 0 disables rewinding [default: 600]
+      --rewind-interval=<n>  Frames between rewind snapshots [default: 2]
+      --trace=<file>         Log every executed instruction with the registers before and after
+      --trace-format=<fmt>   Trace format: text or binary [default: text]
+      --keymap=<file>   TOML file mapping keyboard keys and gamepad buttons to CHIP-8 keys
+      --romdb=<file>    Rom settings searched before the built in database, ~/.config/quartz/romdb.toml if not set
+      --theme=<name>    Display colours: classic, amber, green, lcd, octo
+      --palette=<colours>  Comma separated RRGGBB colours of the background, plane 1, plane 2 and both planes
+      --fg=<colour>     Colour of lit pixels as RRGGBB
+      --bg=<colour>     Background colour as RRGGBB
+      --phosphor=<p>    Fade pixels out to hide flicker, keeping this part of their brightness each frame, from 0.0 to 0.99 [default: 0]
+      --scale=<n>       Initial window size as a multiple of the 64x32 display [default: 10]
+      --vsync           Present frames in sync with the display's refresh
+      --debug           Start paused with a debugger command line on stdin
+      --headless        Run without a window or audio, for automated testing
+      --cycles=<n>      Number of instructions to run headless
+      --frames=<n>      Number of 60Hz frames to run headless
+      --keys=<file>     Key script for headless runs, lines of '<frame> <key> <down|up>'
+      --screenshot=<file>  Save the final display, as PBM if the file ends in .pbm and PNG otherwise
+      --screenshot-scale=<n>  Enlarge screenshots and GIFs this many times [default: 1]
+      --gif=<file>         Record the display to an animated GIF
+      --registers=<file>   Save the final registers as JSON, - for stdout
+      -h --help         Show help.
     ";
 
     #[derive(Debug, Deserialize)]
     pub struct ProgramOptions {
+        pub cmd_disasm: bool,
+        pub cmd_asm: bool,
+        pub arg_source: String,
         pub arg_romfile: String,
-        pub flag_irate: Option<f64>
+        pub flag_irate: Option<f64>,
+        pub flag_ipf: Option<u32>,
+        pub flag_seed: Option<u64>,
+        pub flag_quirks: Option<String>,
+        pub flag_pitch: f32,
+        pub flag_volume: f32,
+        pub flag_load_state: Option<String>,
+        pub flag_record: Option<String>,
+        pub flag_replay: Option<String>,
+        pub flag_rewind_depth: usize,
+        pub flag_rewind_interval: u64,
+        pub flag_trace: Option<String>,
+        pub flag_trace_format: String,
+        pub flag_keymap: Option<String>,
+        pub flag_romdb: Option<String>,
+        pub flag_theme: Option<String>,
+        pub flag_palette: Option<String>,
+        pub flag_fg: Option<String>,
+        pub flag_bg: Option<String>,
+        pub flag_phosphor: f32,
+        pub flag_scale: u32,
+        pub flag_vsync: bool,
+        pub flag_debug: bool,
+        pub flag_headless: bool,
+        pub flag_cycles: Option<u64>,
+        pub flag_frames: Option<u64>,
+        pub flag_keys: Option<String>,
+        pub flag_screenshot: Option<String>,
+        pub flag_screenshot_scale: usize,
+        pub flag_gif: Option<String>,
+        pub flag_registers: Option<String>
     }
 
     pub fn get_program_options() -> ProgramOptions {
@@ -1,32 +1,107 @@
 extern crate quartz;
 extern crate libchip8;
 extern crate sdl2;
+extern crate png;
+extern crate gif;
+extern crate toml;
+extern crate zip;
+#[macro_use]
+extern crate serde_derive;
+
+mod beeper;
+mod font;
+mod gamepad;
+mod gif_recorder;
+mod headless;
+mod keymap;
+mod palette;
+mod phosphor;
+mod picker;
+mod repl;
+mod romdb;
+mod roms;
+mod screenshot;
 
 use libchip8::*;
+use beeper::Beeper;
+use gamepad::Gamepads;
+use gif_recorder::GifRecorder;
+use keymap::Keymap;
+use palette::Palette;
+use phosphor::Phosphor;
+use repl::DebugRepl;
+use romdb::{Game, RomDatabase};
+use roms::{Rom, Source};
+use quartz::options::ProgramOptions;
 
 use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, LALTMOD, LSHIFTMOD, RALTMOD, RSHIFTMOD};
+use sdl2::video::{FullscreenType, Window};
 
 use std::process;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
 use std::error::Error;
-use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Size of the low resolution display, the window starts at a multiple of it
+const DISPLAY_WIDTH: u32 = 64;
+const DISPLAY_HEIGHT: u32 = 32;
+
+/// Instruction rate used if neither it nor the instructions per frame are set
+const DEFAULT_IRATE: f64 = 0.0001;
 
-const WINDOW_WIDTH: u32 = 640;
-const WINDOW_HEIGHT: u32 = 480;
+/// Frames run at once to catch up after a stall, later ones are skipped
+const MAX_CATCH_UP_FRAMES: u32 = 4;
 
 fn main() {
     // get commandline options
     let options = quartz::options::get_program_options();
 
-    // load the rom file into memory
-    let rom = load_rom_file(&options.arg_romfile).unwrap_or_else(
+    if options.cmd_asm {
+        assemble_rom_file(&options.arg_source, &options.arg_romfile);
+        return;
+    }
+
+    // open the rom file, archive or directory
+    let source = roms::open(&options.arg_romfile).unwrap_or_else(
         |e| {
-            println!("{:?}", e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    );
+
+    if options.cmd_disasm || options.flag_headless {
+        let rom = single_rom(source);
+
+        if options.cmd_disasm {
+            print!("{}", listing(&rom.data, PROGRAM_START_ADDRESS));
+            return;
+        }
+
+        let database = load_rom_database(&options);
+        let game = find_game(&database, &hex(&rom_hash(&rom.data)), &rom.name);
+
+        let vm = create_vm(&options, &rom.name, rom.data, game);
+        process::exit(headless::run(vm, &options));
+    }
+
+    run_window(&options, source);
+}
+
+/// Run the rom in a window, picking it from a listing first if `source` is a directory or archive
+fn run_window(options: &ProgramOptions, mut source: Source) {
+    // the listing is drawn in the colours given on the command line
+    let menu_palette = create_palette(options, None).unwrap_or_else(
+        |e| {
+            println!("Invalid colours: {}", e);
             process::exit(1);
         }
     );
@@ -38,42 +113,116 @@ fn main() {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
-    let window = video_subsystem.window("Quartz", WINDOW_WIDTH, WINDOW_HEIGHT)
+    // run without sound if no audio device is available
+    let mut beeper = sdl_context.audio()
+        .and_then(|audio_subsystem| Beeper::new(&audio_subsystem, options.flag_pitch, options.flag_volume))
+        .map_err(|e| println!("Audio disabled: {}", e))
+        .ok();
+
+    // play with the keyboard only if game controllers are not supported
+    let mut gamepads = sdl_context.game_controller()
+        .map(Gamepads::new)
+        .map_err(|e| println!("Game controllers disabled: {}", e))
+        .ok();
+
+    let scale = options.flag_scale.max(1);
+
+    let window = video_subsystem.window("Quartz", DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale)
         .position_centered()
+        .resizable()
         .opengl()
         .build()
         .unwrap();
 
-    let mut canvas = window.into_canvas().build().unwrap();
+    let mut canvas_builder = window.into_canvas();
+
+    if options.flag_vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+
+    let mut canvas = canvas_builder.build().unwrap();
     let texture_creator = canvas.texture_creator();
-    let mut display = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, 64, 32).unwrap();
+    // large enough for the SCHIP high resolution mode, only the active region is drawn
+    let mut display = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, 128, 64).unwrap();
+
+    // RGB24 pixels of the display in the palette's colours
+    let mut frame = Vec::new();
+    let mut phosphor = Phosphor::new(options.flag_phosphor);
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    // pick a rom, going into directories and archives until one is chosen
+    let rom = loop {
+        source = match source {
+            Source::Rom(rom) => break rom,
+            Source::Menu(title, choices) => {
+                let choice = match picker::pick(&mut canvas, &mut event_pump, &mut gamepads, &menu_palette, &title, &choices) {
+                    Some(i) => &choices[i],
+                    None => return
+                };
+
+                match roms::open_choice(choice) {
+                    Ok(source) => source,
+                    Err(e) => {
+                        println!("{}", e);
+                        Source::Menu(title, choices)
+                    }
+                }
+            }
+        };
+    };
+
+    let rom_sha1 = hex(&rom_hash(&rom.data));
+    let database = load_rom_database(options);
+    let game = find_game(&database, &rom_sha1, &rom.name);
+
+    let mut keymap = Keymap::new();
 
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    if let Some(game) = game {
+        keymap.apply(&game.keyboard, &game.gamepad).unwrap_or_else(
+            |e| {
+                println!("Invalid rom database bindings for {}: {}", game.title, e);
+                process::exit(1);
+            }
+        );
+    }
+
+    if let Some(ref keymap_file) = options.flag_keymap {
+        keymap.load(keymap_file, &rom.name, &rom_sha1).unwrap_or_else(
+            |e| {
+                println!("Failed to load keymap {}: {}", keymap_file, e);
+                process::exit(1);
+            }
+        );
+    }
+
+    let palette = create_palette(options, game).unwrap_or_else(
+        |e| {
+            println!("Invalid colours: {}", e);
+            process::exit(1);
+        }
+    );
+
+    let (r, g, b) = palette.get_colour(0);
+    canvas.set_draw_color(Color::RGB(r, g, b));
     canvas.clear();
     canvas.present();
 
-    let event_pump = RefCell::new(sdl_context.event_pump().unwrap());
-
-    let mut key_map = HashMap::new();
-    key_map.insert(Keycode::Q, 0x0);
-    key_map.insert(Keycode::W, 0x1);
-    key_map.insert(Keycode::E, 0x2);
-    key_map.insert(Keycode::R, 0x3);
-    key_map.insert(Keycode::T, 0x4);
-    key_map.insert(Keycode::A, 0x5);
-    key_map.insert(Keycode::S, 0x6);
-    key_map.insert(Keycode::D, 0x7);
-    key_map.insert(Keycode::F, 0x8);
-    key_map.insert(Keycode::G, 0x9);
-    key_map.insert(Keycode::Z, 0xA);
-    key_map.insert(Keycode::X, 0xB);
-    key_map.insert(Keycode::C, 0xC);
-    key_map.insert(Keycode::V, 0xD);
-    key_map.insert(Keycode::B, 0xE);
-    key_map.insert(Keycode::Space, 0xF);
-
     // create the Chip8 virtual machince
-    let mut vm = Chip8::new(options.flag_irate.unwrap());
+    let mut vm = create_vm(options, &rom.name, rom.data, game);
+
+    // SCHIP games keep scores and progress in the RPL user flags, they are kept next to the rom
+    // between runs. Movies start from a clean machine and a save state has flags of its own
+    let flags_file = format!("{}.flags", rom.path);
+    let keep_flags = !vm.is_recording() && !vm.is_replaying();
+
+    if keep_flags && options.flag_load_state.is_none() && Path::new(&flags_file).exists() {
+        if let Err(e) = load_flags(&mut vm, &flags_file) {
+            println!("{}: {}", flags_file, e);
+        }
+    }
+
+    let initial_flags = vm.get_rpl_flags().to_vec();
 
     vm.set_on_display_update(Box::new(
         || {
@@ -81,68 +230,611 @@ fn main() {
         }
     ));
 
-    vm.set_key_wait(Box::new(||{
-        loop {
-            for event in event_pump.borrow_mut().poll_iter() {
-                match event {
-                    Event::KeyDown {keycode, ..} | Event::KeyUp {keycode, ..} => {
-                        if let Some(keycode) = keycode {
-                            if key_map.contains_key(&keycode) {
-                                return key_map[&keycode];
-                            }
-                        }
-                    },
-                    _ => { continue }
-                }
+    // recording to a GIF, from the start with --gif or toggled with F10
+    let mut gif = options.flag_gif.as_ref().map(
+        |file| match GifRecorder::create(file, &palette, options.flag_screenshot_scale) {
+            Ok(recorder) => (file.clone(), recorder),
+            Err(e) => {
+                println!("Failed to record {}: {}", file, e);
+                process::exit(1);
             }
         }
-    }));
+    );
+
+    let mut debug_repl = if options.flag_debug { Some(DebugRepl::new(&mut vm)) } else { None };
+
+    // snapshots for rewinding, stepped back through at the rate they were taken
+    let mut rewind = RewindBuffer::new(options.flag_rewind_depth);
+    let rewind_interval = options.flag_rewind_interval.max(1);
+    let mut rewinding = false;
+    let mut rewind_frame = 0;
+    let mut snapshot_frame = 0;
+    rewind.push(&vm);
+
+    // CHIP-8 keys held down on each controller by instance id, released if it is disconnected
+    let mut controller_keys: HashMap<i32, HashSet<u8>> = HashMap::new();
 
-    vm.load_memory(rom);
+    // the machine runs a frame of instructions every 60th of a second, then the display is presented
+    let frame_period = Duration::new(0, 1_000_000_000 / 60);
+    let mut next_frame = Instant::now();
 
     'running: loop {
 
-        for event in event_pump.borrow_mut().poll_iter() {
+        for event in event_pump.poll_iter() {
             match event {
                 Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running
                 },
-                Event::KeyUp {keycode, ..} => {
-                    if let Some(k) = keycode {
-                        if key_map.contains_key(&k) {
-                            vm.key(key_map[&k], true);
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+                    let file = screenshot::capture_file(&rom.path, "png");
+
+                    match save_screenshot(&vm, &palette, &file, options.flag_screenshot_scale) {
+                        Ok(()) => println!("Saved {}", file),
+                        Err(e) => println!("Failed to write {}: {}", file, e)
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F10), .. } => {
+                    gif = match gif.take() {
+                        Some((file, recorder)) => {
+                            finish_gif(&file, recorder);
+                            None
+                        },
+                        None => {
+                            let file = screenshot::capture_file(&rom.path, "gif");
+
+                            match GifRecorder::create(&file, &palette, options.flag_screenshot_scale) {
+                                Ok(recorder) => {
+                                    println!("Recording {}", file);
+                                    Some((file, recorder))
+                                },
+                                Err(e) => {
+                                    println!("Failed to record {}: {}", file, e);
+                                    None
+                                }
+                            }
                         }
+                    };
+                },
+                Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                    toggle_fullscreen(canvas.window_mut());
+                },
+                Event::KeyDown { keycode: Some(Keycode::Return), keymod, .. } if keymod.intersects(LALTMOD | RALTMOD) => {
+                    toggle_fullscreen(canvas.window_mut());
+                },
+                Event::Window { win_event: WindowEvent::SizeChanged(..), .. } | Event::Window { win_event: WindowEvent::Exposed, .. } => {
+                    // the whole window has to be drawn again
+                    update_display.set(true);
+                },
+                Event::KeyDown { keycode: Some(k), keymod, .. } if state_slot(k).is_some() => {
+                    // F1-F9 save to a slot, holding shift loads from it. Loading would break the
+                    // cycle order of a movie
+                    if vm.is_recording() || vm.is_replaying() {
+                        println!("Save states are disabled while recording or replaying a movie");
+                        continue;
                     }
+
+                    let state_file = format!("{}.state{}", rom.path, state_slot(k).unwrap());
+
+                    let result = if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        load_state(&mut vm, &state_file)
+                    }
+                    else {
+                        save_state(&vm, &state_file)
+                    };
+
+                    if let Err(e) = result {
+                        println!("{}: {}", state_file, e);
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                    rewinding = true;
                 },
-                Event::KeyDown {keycode, ..} =>  {
-                    if let Some(k) = keycode {
-                        if key_map.contains_key(&k) {
-                            vm.key(key_map[&k], false);
+                Event::KeyUp { keycode: Some(Keycode::Backspace), .. } => {
+                    rewinding = false;
+                },
+                Event::KeyDown { scancode: Some(scancode), .. } => {
+                    if let Some(k) = keymap.get_key(scancode) {
+                        vm.key(k, true);
+                    }
+                },
+                Event::KeyUp { scancode: Some(scancode), .. } => {
+                    if let Some(k) = keymap.get_key(scancode) {
+                        vm.key(k, false);
+                    }
+                },
+                Event::ControllerButtonDown { which, button, .. } => {
+                    if let Some(k) = keymap.get_button(button) {
+                        controller_keys.entry(which).or_default().insert(k);
+                        vm.key(k, true);
+                    }
+                },
+                Event::ControllerButtonUp { which, button, .. } => {
+                    if let Some(k) = keymap.get_button(button) {
+                        if let Some(keys) = controller_keys.get_mut(&which) {
+                            keys.remove(&k);
                         }
+                        vm.key(k, false);
+                    }
+                },
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(ref mut gamepads) = gamepads {
+                        gamepads.add(which);
+                    }
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if let Some(ref mut gamepads) = gamepads {
+                        gamepads.remove(which);
+                    }
+
+                    // buttons held on the controller would otherwise stay down
+                    for k in controller_keys.remove(&which).unwrap_or_default() {
+                        vm.key(k, false);
                     }
                 },
                 _ => {}
             }
         }
 
-        if update_display.get() {
+        // run the frames due, a few at once to catch up after a stall
+        let mut frames_run = 0;
+
+        while Instant::now() >= next_frame && frames_run < MAX_CATCH_UP_FRAMES {
+            next_frame += frame_period;
+            frames_run += 1;
+
+            // record the display as it was shown during the last frame
+            let failed = match gif {
+                Some((ref file, ref mut recorder)) => recorder.add_frame(&vm).map_err(|e| println!("Failed to write {}: {}", file, e)).is_err(),
+                None => false
+            };
+
+            if failed {
+                gif = None;
+            }
+
+            // rewinding would break the cycle order of a movie
+            if rewinding && !vm.is_recording() && !vm.is_replaying() {
+                if rewind_frame % rewind_interval == 0 && rewind.pop(&mut vm) {
+                    // keys held in the snapshot would otherwise stay down
+                    for k in 0..16 {
+                        vm.key(k, false);
+                    }
+                }
+
+                rewind_frame += 1;
+                snapshot_frame = vm.get_frames();
+                continue;
+            }
+
+            rewind_frame = 0;
+
+            match debug_repl {
+                Some(ref mut debug_repl) => {
+                    if !debug_repl.run_frame(&mut vm) {
+                        break 'running;
+                    }
+                },
+                None => {
+                    if let Err(e) = vm.run_frame() {
+                        println!("{}", e);
+                        break 'running;
+                    }
+                }
+            }
+
+            // the frame count goes back when a state is loaded
+            let frames = vm.get_frames();
+
+            if frames >= snapshot_frame + rewind_interval || frames < snapshot_frame {
+                rewind.push(&vm);
+                snapshot_frame = frames;
+            }
+        }
+
+        if frames_run == 0 {
+            // wait for the next frame
+            let now = Instant::now();
+
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            }
+
+            continue;
+        }
+
+        // too far behind to catch up, skip the frames that were missed
+        if Instant::now() >= next_frame {
+            next_frame = Instant::now() + frame_period;
+        }
+
+        // present once per frame, and while the phosphor fades out even if the game drew nothing
+        if update_display.get() || phosphor.is_fading() {
+            let width = vm.get_display_width() as u32;
+            let height = vm.get_display_height() as u32;
+            let area = Rect::new(0, 0, width, height);
+
             canvas.clear();
-            display.update(None, vm.get_display_memory(), 64 * 3).unwrap();
-            canvas.copy(&display, None, Some(Rect::new(0,0, WINDOW_WIDTH, WINDOW_HEIGHT))).unwrap();
+            palette.render(&vm, &mut frame);
+            phosphor.apply(&mut frame, vm.get_pixel_values(), palette.get_colour(0));
+            display.update(Some(area), &frame, (width * 3) as usize).unwrap();
+            let output = canvas.output_size().unwrap();
+            canvas.copy(&display, Some(area), Some(letterbox(output, (width, height)))).unwrap();
             canvas.present();
 
             update_display.set(false);
         }
 
-        vm.update().unwrap();
+        if let Some(ref mut beeper) = beeper {
+            beeper.set_active(vm.is_sound_active());
+        }
+
+        // keep the window open to inspect the final state when debugging
+        if vm.is_halted() && debug_repl.is_none() {
+            break 'running;
+        }
+    }
+
+    if let Some((file, recorder)) = gif {
+        finish_gif(&file, recorder);
+    }
+
+    if let Some(ref file) = options.flag_screenshot {
+        if let Err(e) = save_screenshot(&vm, &palette, file, options.flag_screenshot_scale) {
+            println!("Failed to write {}: {}", file, e);
+        }
+    }
+
+    if keep_flags && vm.get_rpl_flags() != initial_flags.as_slice() {
+        if let Err(e) = save_flags(&vm, &flags_file) {
+            println!("Failed to write {}: {}", flags_file, e);
+        }
+    }
+
+    if let Err(e) = save_recording(&mut vm, options) {
+        println!("{}", e);
+        process::exit(1);
     }
 }
 
-fn load_rom_file(rom_file: &String) -> Result<Vec<u8>, Box<Error>> {
-    let mut file = File::open(rom_file)?;
+/// Create the virtual machine with the rom loaded, exits on invalid options. Options that are not
+/// set are taken from the rom database entry `game`
+fn create_vm<'a>(options: &ProgramOptions, rom_name: &str, rom: Vec<u8>, game: Option<&Game>) -> Chip8<'a> {
+    let quirks_name = options.flag_quirks.clone()
+        .or_else(|| game.and_then(|game| game.quirks.clone()))
+        .unwrap_or_else(|| "default".to_string());
+
+    let quirks = Quirks::preset(&quirks_name).unwrap_or_else(
+        || {
+            eprintln!("Unknown quirks preset: {}", quirks_name);
+            process::exit(1);
+        }
+    );
+
+    // XO-CHIP programs can use the full 64 KiB address space
+    let memory_size = if quirks_name == "xochip" { XO_MEMORY_SIZE } else { MEMORY_SIZE };
+
+    let mut vm = Chip8::with_memory_size(options.flag_irate.unwrap_or(DEFAULT_IRATE), memory_size);
+    vm.set_quirks(quirks);
+
+    // an instruction rate on the command line overrides the database's instructions per frame
+    let ipf = match options.flag_irate {
+        Some(_) => options.flag_ipf,
+        None => options.flag_ipf.or_else(|| game.and_then(|game| game.ipf))
+    };
+
+    if let Some(ipf) = ipf {
+        vm.set_instructions_per_frame(ipf);
+    }
+
+    if let Some(seed) = options.flag_seed {
+        vm.set_seed(seed);
+    }
+
+    // a movie only reproduces a run started from the rom
+    if options.flag_load_state.is_some() && (options.flag_record.is_some() || options.flag_replay.is_some()) {
+        eprintln!("--load-state cannot be used with --record or --replay");
+        process::exit(1);
+    }
+
+    if let Some(ref movie_file) = options.flag_replay {
+        load_movie(movie_file).and_then(|movie| Ok(movie.replay(&mut vm, &rom)?)).unwrap_or_else(
+            |e| {
+                eprintln!("Failed to replay {}: {}", movie_file, e);
+                process::exit(1);
+            }
+        );
+    }
+
+    if options.flag_record.is_some() {
+        vm.start_recording(&rom);
+    }
+
+    vm.load_memory(rom).unwrap_or_else(
+        |e| {
+            eprintln!("{}: {}", rom_name, e);
+            process::exit(1);
+        }
+    );
+
+    if let Some(ref trace_file) = options.flag_trace {
+        start_trace(&mut vm, trace_file, &options.flag_trace_format).unwrap_or_else(
+            |e| {
+                eprintln!("Failed to trace to {}: {}", trace_file, e);
+                process::exit(1);
+            }
+        );
+    }
+
+    if let Some(ref state_file) = options.flag_load_state {
+        load_state(&mut vm, state_file).unwrap_or_else(
+            |e| {
+                eprintln!("Failed to load state {}: {}", state_file, e);
+                process::exit(1);
+            }
+        );
+    }
+
+    vm
+}
+
+/// Load the built in rom database and the user's, exits if a database given on the command line
+/// cannot be read
+fn load_rom_database(options: &ProgramOptions) -> RomDatabase {
+    let mut database = RomDatabase::new();
+
+    match options.flag_romdb {
+        Some(ref file) => database.load(file).unwrap_or_else(
+            |e| {
+                eprintln!("Failed to load rom database {}: {}", file, e);
+                process::exit(1);
+            }
+        ),
+        None => {
+            // the default user database is optional
+            if let Some(path) = romdb::user_database_path().filter(|path| path.exists()) {
+                if let Err(e) = database.load(&path.to_string_lossy()) {
+                    eprintln!("Failed to load rom database {}: {}", path.display(), e);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+
+    database
+}
+
+/// Build the display palette from the theme and colours given on the command line, over the ones
+/// in the rom database entry `game`
+fn create_palette(options: &ProgramOptions, game: Option<&Game>) -> Result<Palette, String> {
+    let mut palette = Palette::new();
+
+    let layers = [
+        game.map_or((None, None, None, None), |game| (game.theme.as_ref(), game.palette.as_ref(), game.fg.as_ref(), game.bg.as_ref())),
+        (options.flag_theme.as_ref(), options.flag_palette.as_ref(), options.flag_fg.as_ref(), options.flag_bg.as_ref())
+    ];
+
+    for &(theme, colours, fg, bg) in layers.iter() {
+        if let Some(theme) = theme {
+            palette = Palette::theme(theme).ok_or_else(
+                || format!("unknown theme '{}', expected one of {}", theme, Palette::theme_names().join(", "))
+            )?;
+        }
+
+        if let Some(colours) = colours {
+            palette.set_colours(colours)?;
+        }
+
+        if let Some(fg) = fg {
+            palette.set_colour(1, palette::parse_colour(fg)?);
+        }
+
+        if let Some(bg) = bg {
+            palette.set_colour(0, palette::parse_colour(bg)?);
+        }
+    }
+
+    Ok(palette)
+}
+
+/// The rom of a source that cannot show a listing, exits listing the roms to choose from if there
+/// are several
+fn single_rom(source: Source) -> Rom {
+    match source {
+        Source::Rom(rom) => rom,
+        Source::Menu(title, choices) => {
+            eprintln!("Choose a rom from {}:", title);
+
+            for choice in choices.iter() {
+                eprintln!("  {}", choice.label);
+            }
+
+            process::exit(1);
+        }
+    }
+}
+
+/// Find the rom database entry of a rom
+fn find_game<'a>(database: &'a RomDatabase, rom_sha1: &str, rom_name: &str) -> Option<&'a Game> {
+    let game = database.find(rom_sha1, rom_name);
+
+    // on stderr, headless runs can write the registers to stdout
+    if let Some(game) = game {
+        eprintln!("Using rom database settings for {}", game.title);
+    }
+
+    game
+}
+
+/// Assemble a source file and write the rom, exits on errors
+fn assemble_rom_file(source_file: &str, rom_file: &str) {
+    let rom = assemble_file(source_file).unwrap_or_else(
+        |e| {
+            println!("{}", e);
+            process::exit(1);
+        }
+    );
+
+    File::create(rom_file).and_then(|mut file| file.write_all(&rom)).unwrap_or_else(
+        |e| {
+            println!("Failed to write {}: {}", rom_file, e);
+            process::exit(1);
+        }
+    );
+}
+
+fn save_state(vm: &Chip8, state_file: &str) -> Result<(), Box<Error>> {
+    let mut file = File::create(state_file)?;
+    file.write_all(&vm.save_state())?;
+
+    Ok(())
+}
+
+fn load_state(vm: &mut Chip8, state_file: &str) -> Result<(), Box<Error>> {
+    let mut file = File::open(state_file)?;
 
     let mut buffer: Vec<u8> = Vec::new();
-    file.read_to_end(&mut buffer).unwrap();
+    file.read_to_end(&mut buffer)?;
+
+    vm.load_state(&buffer)?;
+
+    Ok(())
+}
 
-    Ok(buffer)
+fn save_flags(vm: &Chip8, flags_file: &str) -> Result<(), Box<Error>> {
+    let mut file = File::create(flags_file)?;
+    file.write_all(vm.get_rpl_flags())?;
+
+    Ok(())
+}
+
+fn load_flags(vm: &mut Chip8, flags_file: &str) -> Result<(), Box<Error>> {
+    let mut file = File::open(flags_file)?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    vm.set_rpl_flags(&buffer);
+
+    Ok(())
+}
+
+fn load_movie(movie_file: &str) -> Result<Movie, Box<Error>> {
+    let mut file = File::open(movie_file)?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    Ok(Movie::from_bytes(&buffer)?)
+}
+
+/// Write the movie of the run if it was recorded
+fn save_recording(vm: &mut Chip8, options: &ProgramOptions) -> Result<(), String> {
+    match (options.flag_record.as_ref(), vm.stop_recording()) {
+        (Some(movie_file), Some(movie)) => {
+            File::create(movie_file).and_then(|mut file| file.write_all(&movie.to_bytes())).map_err(
+                |e| format!("Failed to write {}: {}", movie_file, e)
+            )
+        },
+        _ => Ok(())
+    }
+}
+
+/// Write a trace of every executed instruction to `trace_file`, as text lines or binary entries
+fn start_trace(vm: &mut Chip8, trace_file: &str, format: &str) -> Result<(), Box<Error>> {
+    let binary = match format {
+        "text" => false,
+        "binary" => true,
+        _ => return Err(From::from(format!("unknown trace format '{}'", format)))
+    };
+
+    let mut writer = BufWriter::new(File::create(trace_file)?);
+    let mut failed = false;
+
+    vm.set_tracer(Box::new(move |entry: &TraceEntry| {
+        if failed {
+            return;
+        }
+
+        let written = if binary {
+            writer.write_all(&entry.to_bytes())
+        }
+        else {
+            writeln!(writer, "{}", entry.to_text())
+        };
+
+        // report the first failure only, the run goes on without the trace
+        if let Err(e) = written {
+            eprintln!("Failed to write trace: {}", e);
+            failed = true;
+        }
+    }));
+
+    Ok(())
+}
+
+/// Save the display in the colours of the palette, as a PBM if the file ends in .pbm
+fn save_screenshot(vm: &Chip8, palette: &Palette, file: &str, scale: usize) -> Result<(), Box<Error>> {
+    if file.to_lowercase().ends_with(".pbm") {
+        return screenshot::save(vm, file, scale);
+    }
+
+    let mut pixels = Vec::new();
+    palette.render(vm, &mut pixels);
+
+    screenshot::save_png(file, &pixels, vm.get_display_width(), vm.get_display_height(), scale)
+}
+
+/// End a GIF recording
+fn finish_gif(file: &str, recorder: GifRecorder) {
+    match recorder.finish() {
+        Ok(()) => println!("Saved {}", file),
+        Err(e) => println!("Failed to write {}: {}", file, e)
+    }
+}
+
+/// Lower case hex string of `bytes`
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Largest area of the window showing the display at an integer scale, centered
+fn letterbox(output: (u32, u32), display: (u32, u32)) -> Rect {
+    let (output_width, output_height) = output;
+    let (width, height) = display;
+
+    // a window smaller than the display still shows all of it
+    let scale = (output_width / width).min(output_height / height).max(1);
+
+    let x = (output_width as i32 - (width * scale) as i32) / 2;
+    let y = (output_height as i32 - (height * scale) as i32) / 2;
+
+    Rect::new(x, y, width * scale, height * scale)
+}
+
+/// Switch between a window and fullscreen at the desktop resolution
+fn toggle_fullscreen(window: &mut Window) {
+    let fullscreen = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off
+    };
+
+    if let Err(e) = window.set_fullscreen(fullscreen) {
+        println!("Failed to toggle fullscreen: {}", e);
+    }
+}
+
+/// Save state slot selected by a function key
+fn state_slot(keycode: Keycode) -> Option<u8> {
+    match keycode {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        Keycode::F5 => Some(5),
+        Keycode::F6 => Some(6),
+        Keycode::F7 => Some(7),
+        Keycode::F8 => Some(8),
+        Keycode::F9 => Some(9),
+        _ => None
+    }
 }
@@ -0,0 +1,115 @@
+//! Display colours
+//!
+//! A palette has a colour for each logical pixel value: the background, pixels on in the first
+//! plane, pixels on in the second XO-CHIP plane and pixels on in both.
+
+use libchip8::Chip8;
+
+pub type Colour = (u8, u8, u8);
+
+/// Named palettes, the first one is the default
+const THEMES: [(&'static str, [Colour; 4]); 5] = [
+    ("classic", [(0x00, 0x00, 0x00), (0xFF, 0xFF, 0xFF), (0xAA, 0xAA, 0xAA), (0x55, 0x55, 0x55)]),
+    ("amber",   [(0x1A, 0x0F, 0x00), (0xFF, 0xB0, 0x00), (0xB3, 0x6B, 0x00), (0x66, 0x3D, 0x00)]),
+    ("green",   [(0x00, 0x1A, 0x08), (0x33, 0xFF, 0x66), (0x1F, 0x99, 0x3D), (0x0F, 0x4D, 0x1F)]),
+    ("lcd",     [(0x9B, 0xBC, 0x0F), (0x0F, 0x38, 0x0F), (0x30, 0x62, 0x30), (0x8B, 0xAC, 0x0F)]),
+    ("octo",    [(0x99, 0x66, 0x00), (0xFF, 0xCC, 0x00), (0xFF, 0x66, 0x00), (0x66, 0x22, 0x00)])
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    colours: [Colour; 4]
+}
+
+impl Palette {
+    /// The default palette, white on black
+    pub fn new() -> Self {
+        Palette {
+            colours: THEMES[0].1
+        }
+    }
+
+    /// Look up a palette by name
+    pub fn theme(name: &str) -> Option<Palette> {
+        THEMES.iter().find(|theme| theme.0 == name).map(|theme| Palette { colours: theme.1 })
+    }
+
+    /// Names of the themes
+    pub fn theme_names() -> Vec<&'static str> {
+        THEMES.iter().map(|theme| theme.0).collect()
+    }
+
+    /// Parse a list of up to four colours, separated by commas, replacing the colours of the pixel
+    /// values 0, 1, 2 and 3 in order
+    pub fn set_colours(&mut self, colours: &str) -> Result<(), String> {
+        let colours: Vec<&str> = colours.split(',').collect();
+
+        if colours.len() > 4 {
+            return Err(format!("expected at most 4 colours, found {}", colours.len()));
+        }
+
+        for (value, colour) in colours.iter().enumerate() {
+            self.colours[value] = parse_colour(colour)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_colour(&mut self, value: u8, colour: Colour) {
+        self.colours[value as usize & 0x3] = colour;
+    }
+
+    pub fn get_colour(&self, value: u8) -> Colour {
+        self.colours[value as usize & 0x3]
+    }
+
+    /// Colour the displayed pixels of the machine into an RGB24 buffer
+    pub fn render(&self, vm: &Chip8, buffer: &mut Vec<u8>) {
+        buffer.clear();
+
+        for value in vm.get_pixel_values().iter() {
+            let (r, g, b) = self.get_colour(*value);
+            buffer.extend_from_slice(&[r, g, b]);
+        }
+    }
+}
+
+/// Parse a colour written as `RRGGBB` hex, optionally starting with `#`
+pub fn parse_colour(colour: &str) -> Result<Colour, String> {
+    let hex = colour.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+    let invalid = || format!("'{}' is not a colour, expected RRGGBB hex", colour);
+
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+
+    Ok(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_colour() {
+        assert_eq!(parse_colour("#FF8000"), Ok((0xFF, 0x80, 0x00)));
+        assert_eq!(parse_colour("0a0b0c"), Ok((0x0A, 0x0B, 0x0C)));
+        assert!(parse_colour("#FFF").is_err());
+        assert!(parse_colour("+12345").is_err());
+    }
+
+    #[test]
+    fn test_set_colours() {
+        let mut palette = Palette::theme("octo").unwrap();
+        palette.set_colours("000000,#FFFFFF").unwrap();
+
+        assert_eq!(palette.get_colour(0), (0, 0, 0));
+        assert_eq!(palette.get_colour(1), (255, 255, 255));
+        assert_eq!(palette.get_colour(2), (0xFF, 0x66, 0x00));
+        assert!(palette.set_colours("0,0,0,0,0").is_err());
+    }
+}
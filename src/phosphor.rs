@@ -0,0 +1,86 @@
+//! Phosphor persistence
+//!
+//! Games erase and redraw sprites with XOR, so moving sprites flicker. Like the phosphor of a CRT,
+//! pixels light up at once but fade out over a few frames, which hides the flicker. The effect is
+//! applied to the RGB24 frame on the CPU, before it is copied to the texture.
+
+use palette::Colour;
+
+use std::time::Instant;
+
+pub struct Phosphor {
+    persistence: f32,
+    colours: Vec<f32>, // displayed colour of every pixel channel
+    fading: bool,      // some pixels have not faded out yet
+    last_update: Instant
+}
+
+impl Phosphor {
+    /// `persistence` is the part of a pixel's brightness left after a 60Hz frame, 0 turns the effect off
+    pub fn new(persistence: f32) -> Self {
+        Phosphor {
+            persistence: persistence.clamp(0.0, 0.99),
+            colours: Vec::new(),
+            fading: false,
+            last_update: Instant::now()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.persistence > 0.0
+    }
+
+    /// Pixels are still fading out, the frame should be redrawn even if the display did not change
+    pub fn is_fading(&self) -> bool {
+        self.fading
+    }
+
+    /// Blend `frame` with the fading pixels of the previous frames. `values` are the logical pixel
+    /// values of the frame, unlit pixels fade to `background`
+    pub fn apply(&mut self, frame: &mut [u8], values: &[u8], background: Colour) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update);
+        self.last_update = now;
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        // the display changed resolution, there is nothing to fade from
+        if self.colours.len() != frame.len() {
+            self.colours = frame.iter().map(|c| *c as f32).collect();
+            self.fading = false;
+            return;
+        }
+
+        let frames = (elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1e9) * 60.0;
+        let decay = self.persistence.powf(frames);
+        let background = [background.0 as f32, background.1 as f32, background.2 as f32];
+
+        self.fading = false;
+
+        for (pixel, value) in values.iter().enumerate() {
+            for (channel, background) in background.iter().enumerate() {
+                let i = pixel * 3 + channel;
+
+                let colour = if *value != 0 {
+                    frame[i] as f32
+                }
+                else {
+                    let faded = background + (self.colours[i] - background) * decay;
+
+                    if (faded - background).abs() < 1.0 {
+                        *background
+                    }
+                    else {
+                        self.fading = true;
+                        faded
+                    }
+                };
+
+                self.colours[i] = colour;
+                frame[i] = colour.round() as u8;
+            }
+        }
+    }
+}
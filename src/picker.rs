@@ -0,0 +1,158 @@
+//! Picking a rom from a directory or archive listing in the window
+//!
+//! The listing is drawn with the CHIP-8 font on a display four times the size of the low
+//! resolution one, in the colours of the palette. The selected entry is drawn inverted.
+
+use font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use gamepad::Gamepads;
+use palette::Palette;
+use roms::Choice;
+use {letterbox, toggle_fullscreen};
+
+use sdl2::EventPump;
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, LALTMOD, RALTMOD};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 128;
+
+const MARGIN: usize = 2;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+/// Top of the first entry, below the title
+const LIST_TOP: usize = MARGIN + LINE_HEIGHT * 2;
+/// Entries shown at once
+const ROWS: usize = (HEIGHT - LIST_TOP) / LINE_HEIGHT;
+/// Characters that fit on a line
+const COLUMNS: usize = (WIDTH - MARGIN * 2) / (GLYPH_WIDTH + 1);
+
+/// Show the `choices` under `title` until one is picked with Enter or a controller's A or Start
+/// button. Returns its index, or `None` if the window was closed or the pick cancelled with Escape
+pub fn pick(canvas: &mut Canvas<Window>, event_pump: &mut EventPump, gamepads: &mut Option<Gamepads>,
+            palette: &Palette, title: &str, choices: &[Choice]) -> Option<usize> {
+    if choices.is_empty() {
+        println!("{}: No roms found", title);
+        return None;
+    }
+
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, WIDTH as u32, HEIGHT as u32).unwrap();
+
+    let (r, g, b) = palette.get_colour(0);
+    canvas.set_draw_color(Color::RGB(r, g, b));
+
+    let last = choices.len() - 1;
+    let mut selected = 0;
+
+    loop {
+        let frame = render(palette, title, choices, selected);
+        texture.update(None, &frame, WIDTH * 3).unwrap();
+
+        canvas.clear();
+        let output = canvas.output_size().unwrap();
+        canvas.copy(&texture, None, Some(letterbox(output, (WIDTH as u32, HEIGHT as u32)))).unwrap();
+        canvas.present();
+
+        // redraw after every event, window events included
+        match event_pump.wait_event() {
+            Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                return None;
+            },
+            Event::KeyDown { keycode: Some(Keycode::Return), keymod, .. } if keymod.intersects(LALTMOD | RALTMOD) => {
+                toggle_fullscreen(canvas.window_mut());
+            },
+            Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                toggle_fullscreen(canvas.window_mut());
+            },
+            Event::KeyDown { keycode: Some(Keycode::Return), .. } | Event::KeyDown { keycode: Some(Keycode::KpEnter), .. } => {
+                return Some(selected);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                selected = selected.saturating_sub(1);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                selected = (selected + 1).min(last);
+            },
+            Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => {
+                selected = selected.saturating_sub(ROWS);
+            },
+            Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => {
+                selected = (selected + ROWS).min(last);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Home), .. } => {
+                selected = 0;
+            },
+            Event::KeyDown { keycode: Some(Keycode::End), .. } => {
+                selected = last;
+            },
+            Event::ControllerButtonDown { button, .. } => {
+                match button {
+                    Button::A | Button::Start => return Some(selected),
+                    Button::DPadUp => selected = selected.saturating_sub(1),
+                    Button::DPadDown => selected = (selected + 1).min(last),
+                    Button::DPadLeft => selected = selected.saturating_sub(ROWS),
+                    Button::DPadRight => selected = (selected + ROWS).min(last),
+                    _ => {}
+                }
+            },
+            Event::ControllerDeviceAdded { which, .. } => {
+                if let Some(ref mut gamepads) = *gamepads {
+                    gamepads.add(which);
+                }
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(ref mut gamepads) = *gamepads {
+                    gamepads.remove(which);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// RGB24 pixels of the listing, scrolled to show the selected entry
+fn render(palette: &Palette, title: &str, choices: &[Choice], selected: usize) -> Vec<u8> {
+    let mut pixels = vec![0; WIDTH * HEIGHT];
+
+    // the end of a long path says more than its start
+    let title: Vec<char> = title.chars().collect();
+    let title: String = if title.len() > COLUMNS {
+        title[(title.len() - COLUMNS)..].iter().collect()
+    }
+    else {
+        title.iter().collect()
+    };
+
+    font::draw_text(&mut pixels, WIDTH, MARGIN, MARGIN, &title, 1);
+
+    let first = (selected + 1).saturating_sub(ROWS);
+
+    for (row, (i, choice)) in choices.iter().enumerate().skip(first).take(ROWS).enumerate() {
+        let y = LIST_TOP + row * LINE_HEIGHT;
+        let label: String = choice.label.chars().take(COLUMNS).collect();
+
+        if i == selected {
+            for line in (y - 1)..(y + GLYPH_HEIGHT + 1) {
+                for pixel in &mut pixels[(line * WIDTH)..((line + 1) * WIDTH)] {
+                    *pixel = 1;
+                }
+            }
+
+            font::draw_text(&mut pixels, WIDTH, MARGIN, y, &label, 0);
+        }
+        else {
+            font::draw_text(&mut pixels, WIDTH, MARGIN, y, &label, 1);
+        }
+    }
+
+    let mut frame = Vec::with_capacity(pixels.len() * 3);
+    for value in pixels {
+        let (r, g, b) = palette.get_colour(value);
+        frame.extend_from_slice(&[r, g, b]);
+    }
+
+    frame
+}
@@ -0,0 +1,276 @@
+use libchip8::{Chip8, disassemble};
+use libchip8::debugger::*;
+
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const HELP: &'static str = "\
+Commands:
+  c, continue              Resume execution
+  p, pause                 Pause execution
+  s, step                  Execute one instruction
+  n, next                  Step over a subroutine call
+  f, finish                Run until the current subroutine returns
+  b, break <addr>          Set a breakpoint
+  d, delete <addr>         Delete a breakpoint
+  w, watch <addr> [r|w|rw] Watch memory reads and/or writes, defaults to rw
+  u, unwatch <addr>        Delete a watchpoint
+  cond V<x> <op> <value>   Break when a register comparison (==, !=, <, >) becomes true
+  uncond <index>           Delete a register condition
+  i, info                  List breakpoints, watchpoints and conditions
+  r, regs                  Show registers
+  m, mem <addr> [len]      Dump memory
+  q, quit                  Exit quartz
+  h, help                  Show this help
+Addresses and values are hexadecimal.";
+
+/// Debugger command line read from stdin while the emulator runs
+pub struct DebugRepl {
+    debugger: Debugger,
+    commands: Receiver<String>,
+    paused: bool
+}
+
+impl DebugRepl {
+    /// Attach a debugger to the machine and start reading commands. Execution starts paused
+    pub fn new(vm: &mut Chip8) -> Self {
+        let mut debugger = Debugger::new();
+        debugger.attach(vm);
+
+        let (tx, rx) = mpsc::channel();
+
+        // stdin blocks, read it on another thread so the window stays responsive
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => if tx.send(line).is_err() { break },
+                    Err(_) => break
+                }
+            }
+        });
+
+        println!("Debugger attached, type 'help' for commands");
+        print_location(vm);
+
+        DebugRepl {
+            debugger: debugger,
+            commands: rx,
+            paused: true
+        }
+    }
+
+    /// Run pending commands and the rest of the machine's frame if it is not paused.
+    /// Returns false if the user asked to quit
+    pub fn run_frame(&mut self, vm: &mut Chip8) -> bool {
+        while let Ok(line) = self.commands.try_recv() {
+            if !self.execute(vm, &line) {
+                return false;
+            }
+        }
+
+        if !self.paused {
+            match self.debugger.run_frame(vm) {
+                Ok(Some(reason)) => self.stop(vm, Ok(reason)),
+                Ok(None) => {},
+                Err(e) => self.stop(vm, Err(e))
+            }
+        }
+
+        true
+    }
+
+    fn execute(&mut self, vm: &mut Chip8, line: &str) -> bool {
+        let args: Vec<&str> = line.split_whitespace().collect();
+
+        if args.is_empty() {
+            return true;
+        }
+
+        match (args[0], &args[1..]) {
+            ("c", []) | ("continue", []) => {
+                self.paused = false;
+            },
+            ("p", []) | ("pause", []) => {
+                self.paused = true;
+                print_location(vm);
+            },
+            ("s", []) | ("step", []) => {
+                let result = self.debugger.step(vm);
+                self.stop(vm, result);
+            },
+            ("n", []) | ("next", []) => {
+                let result = self.debugger.step_over(vm);
+                self.stop(vm, result);
+            },
+            ("f", []) | ("finish", []) => {
+                let result = self.debugger.step_out(vm);
+                self.stop(vm, result);
+            },
+            ("b", [addr]) | ("break", [addr]) => {
+                match parse_hex(addr) {
+                    Some(addr) => self.debugger.add_breakpoint(addr),
+                    None => println!("Invalid address: {}", addr)
+                }
+            },
+            ("d", [addr]) | ("delete", [addr]) => {
+                match parse_hex(addr) {
+                    Some(addr) if self.debugger.remove_breakpoint(addr) => {},
+                    _ => println!("No breakpoint at {}", addr)
+                }
+            },
+            ("w", _) | ("watch", _) if args.len() == 2 || args.len() == 3 => {
+                let watch = match args.get(2) {
+                    None | Some(&"rw") => Some(Watch::ReadWrite),
+                    Some(&"r") => Some(Watch::Read),
+                    Some(&"w") => Some(Watch::Write),
+                    _ => None
+                };
+
+                match (parse_hex(args[1]), watch) {
+                    (Some(addr), Some(watch)) => self.debugger.add_watchpoint(addr, watch),
+                    _ => println!("Usage: watch <addr> [r|w|rw]")
+                }
+            },
+            ("u", [addr]) | ("unwatch", [addr]) => {
+                match parse_hex(addr) {
+                    Some(addr) if self.debugger.remove_watchpoint(addr) => {},
+                    _ => println!("No watchpoint at {}", addr)
+                }
+            },
+            ("cond", [register, op, value]) => {
+                match parse_condition(register, op, value) {
+                    Some(condition) => {
+                        let index = self.debugger.add_condition(condition);
+                        println!("Condition {} added", index);
+                    },
+                    None => println!("Usage: cond V<x> <op> <value>")
+                }
+            },
+            ("uncond", [index]) => {
+                match index.parse::<usize>() {
+                    Ok(index) if self.debugger.remove_condition(index) => {},
+                    _ => println!("No condition {}", index)
+                }
+            },
+            ("i", []) | ("info", []) => {
+                self.print_info();
+            },
+            ("r", []) | ("regs", []) => {
+                print_registers(vm);
+            },
+            ("m", _) | ("mem", _) if args.len() == 2 || args.len() == 3 => {
+                let len = args.get(2).map_or(Some(16), |len| parse_hex(len));
+
+                match (parse_hex(args[1]), len) {
+                    (Some(addr), Some(len)) => print_memory(vm, addr as usize, len as usize),
+                    _ => println!("Usage: mem <addr> [len]")
+                }
+            },
+            ("q", []) | ("quit", []) => {
+                return false;
+            },
+            ("h", []) | ("help", []) => {
+                println!("{}", HELP);
+            },
+            _ => {
+                println!("Unknown command '{}', type 'help' for commands", line.trim());
+            }
+        }
+
+        true
+    }
+
+    fn stop(&mut self, vm: &Chip8, result: Result<StopReason, ::libchip8::Chip8Error>) {
+        self.paused = true;
+
+        match result {
+            Ok(StopReason::Step) => {},
+            Ok(StopReason::Breakpoint(addr)) => println!("Breakpoint at {:03X}", addr),
+            Ok(StopReason::Watchpoint(access)) => println!("Watchpoint: {:?} of {:03X}", access.access, access.addr),
+            Ok(StopReason::Condition(index)) => println!("Condition {} met", index),
+            Ok(StopReason::Halted) => println!("Program exited"),
+            Ok(StopReason::StepLimit) => println!("Step limit reached"),
+            Err(e) => println!("{}", e)
+        }
+
+        print_location(vm);
+    }
+
+    fn print_info(&self) {
+        for addr in self.debugger.get_breakpoints() {
+            println!("break {:03X}", addr);
+        }
+
+        for (addr, watch) in self.debugger.get_watchpoints() {
+            println!("watch {:03X} {:?}", addr, watch);
+        }
+
+        for (index, condition) in self.debugger.get_conditions().iter().enumerate() {
+            println!("cond {}: V{:X} {:?} {:02X}", index, condition.register, condition.comparison, condition.value);
+        }
+    }
+}
+
+fn print_location(vm: &Chip8) {
+    let pc = vm.get_pc() as usize;
+    let end = (pc + 4).min(vm.get_memory_size());
+    let bytes: Vec<u8> = (pc..end).map(|addr| vm.get_memory(addr)).collect();
+
+    // PC can be on the last byte of memory or past its end, missing bytes read as zero and show ???
+    let byte = |i: usize| *bytes.get(i).unwrap_or(&0) as u16;
+    let opcode = (byte(0) << 8) | byte(1);
+
+    match disassemble(&bytes, pc as u16).first() {
+        Some(&(addr, instr)) if addr as usize == pc => println!("{:03X}: {:04X}  {}", pc, opcode, instr),
+        _ => println!("{:03X}: {:04X}  ???", pc, opcode)
+    }
+}
+
+fn print_registers(vm: &Chip8) {
+    let registers: Vec<String> = (0..16).map(|x| format!("V{:X}={:02X}", x, vm.get_register(x))).collect();
+
+    println!("{}", registers.join(" "));
+    println!("PC={:03X} I={:03X} SP={:X} DT={:02X} ST={:02X}", vm.get_pc(), vm.get_i(), vm.get_sp(), vm.get_dt(), vm.get_st());
+}
+
+fn print_memory(vm: &Chip8, addr: usize, len: usize) {
+    let end = (addr + len).min(vm.get_memory_size());
+
+    for row in (addr..end).step_by(16) {
+        let bytes: Vec<String> = (row..(row + 16).min(end)).map(|a| format!("{:02X}", vm.get_memory(a))).collect();
+        println!("{:03X}: {}", row, bytes.join(" "));
+    }
+}
+
+/// Parse a hexadecimal number, with an optional `0x` or `$` prefix
+fn parse_hex(s: &str) -> Option<u16> {
+    let digits = s.trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn parse_condition(register: &str, op: &str, value: &str) -> Option<RegisterCondition> {
+    let register = if register.starts_with('V') || register.starts_with('v') {
+        usize::from_str_radix(&register[1..], 16).ok().filter(|&x| x < 16)?
+    }
+    else {
+        return None;
+    };
+
+    let comparison = match op {
+        "==" => Comparison::Equal,
+        "!=" => Comparison::NotEqual,
+        "<" => Comparison::Less,
+        ">" => Comparison::Greater,
+        _ => return None
+    };
+
+    let value = parse_hex(value).filter(|&v| v <= 0xFF)? as u8;
+
+    Some(RegisterCondition {
+        register: register,
+        comparison: comparison,
+        value: value
+    })
+}
@@ -0,0 +1,119 @@
+//! Per-rom settings database
+//!
+//! Games are looked up by the SHA-1 of the rom, then by its file name. The database shipped with
+//! quartz is built in, a user database is searched before it so its entries replace the built in
+//! ones. See `romdb.toml` for the format.
+
+use toml;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// The database shipped with quartz
+const BUILTIN_DATABASE: &'static str = include_str!("../romdb.toml");
+
+/// Settings for a game
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Game {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub sha1: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<String>,
+    pub quirks: Option<String>,
+    pub ipf: Option<u32>,
+    pub theme: Option<String>,
+    pub palette: Option<String>,
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    #[serde(default)]
+    pub keyboard: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub gamepad: HashMap<String, Vec<String>>
+}
+
+#[derive(Debug, Deserialize)]
+struct DatabaseFile {
+    #[serde(default)]
+    games: Vec<Game>
+}
+
+pub struct RomDatabase {
+    games: Vec<Game> // searched in order, user entries first
+}
+
+impl RomDatabase {
+    /// The built in database
+    pub fn new() -> Self {
+        RomDatabase {
+            games: parse(BUILTIN_DATABASE).expect("invalid built in rom database")
+        }
+    }
+
+    /// Add the entries of a user database, they take precedence over the ones already loaded
+    pub fn load(&mut self, file: &str) -> Result<(), String> {
+        let mut contents = String::new();
+        File::open(file).and_then(|mut f| f.read_to_string(&mut contents)).map_err(|e| e.to_string())?;
+
+        let mut games = parse(&contents)?;
+        games.append(&mut self.games);
+        self.games = games;
+
+        Ok(())
+    }
+
+    /// Find the settings for the rom hashed `rom_hash` and named `rom_file`
+    pub fn find(&self, rom_hash: &str, rom_file: &str) -> Option<&Game> {
+        let name = Path::new(rom_file).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_lowercase());
+
+        self.games.iter().find(|game| game.sha1.iter().any(|sha1| sha1.to_lowercase() == rom_hash))
+            .or_else(|| self.games.iter().find(|game| game.files.iter().any(|file| file.to_lowercase() == name)))
+    }
+}
+
+/// Location of the user database, `quartz/romdb.toml` in the user's config directory
+pub fn user_database_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .map(|config| config.join("quartz").join("romdb.toml"))
+}
+
+fn parse(contents: &str) -> Result<Vec<Game>, String> {
+    let database: DatabaseFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+    Ok(database.games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_database() {
+        let database = RomDatabase::new();
+
+        // shipped entries are matched by checked hashes, not only by file name
+        assert!(database.games.iter().all(|game| !game.sha1.is_empty()));
+        assert!(database.find("", "unknown.ch8").is_none());
+    }
+
+    #[test]
+    fn test_file_name() {
+        let mut database = RomDatabase::new();
+        database.games.push(Game { title: "Space Invaders".to_string(), files: vec!["Invaders".to_string()], ..Game::default() });
+
+        assert_eq!(database.find("", "roms/INVADERS.ch8").map(|game| game.title.as_str()), Some("Space Invaders"));
+    }
+
+    #[test]
+    fn test_hash_before_file_name() {
+        let mut database = RomDatabase::new();
+        database.games.push(Game { title: "Hashed".to_string(), sha1: vec!["ABCD".to_string()], ..Game::default() });
+
+        assert_eq!(database.find("abcd", "tetris.ch8").map(|game| game.title.as_str()), Some("Hashed"));
+    }
+}
@@ -0,0 +1,183 @@
+//! Reading roms from files, ZIP archives and stdin, and listing the roms of directories and
+//! archives to pick from
+//!
+//! A rom inside an archive can be given as a path through it, `games.zip/PONG.ch8`.
+
+use zip::ZipArchive;
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files listed as roms, files without an extension are listed too
+const ROM_EXTENSIONS: [&'static str; 6] = ["ch8", "c8", "sc8", "xo8", "ch48", "rom"];
+
+pub struct Rom {
+    /// File name the rom is looked up by in the rom database and keymap
+    pub name: String,
+    /// Path that files saved for the rom, states and captures, are named after
+    pub path: String,
+    pub data: Vec<u8>
+}
+
+/// An entry of a directory or archive listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Choice {
+    pub label: String,
+    path: PathBuf,
+    entry: Option<String> // name of the file in the archive at `path`
+}
+
+pub enum Source {
+    Rom(Rom),
+    /// A directory, or an archive of several roms, with a title and the entries to pick from
+    Menu(String, Vec<Choice>)
+}
+
+/// Open a rom file, an archive, a directory or `-` for stdin
+pub fn open(path: &str) -> Result<Source, String> {
+    if path == "-" {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map_err(|e| format!("Failed to read stdin: {}", e))?;
+
+        return Ok(Source::Rom(Rom {
+            name: "stdin".to_string(),
+            path: "stdin".to_string(),
+            data: data
+        }));
+    }
+
+    let file = Path::new(path);
+
+    if file.is_dir() {
+        return list_directory(file).map(|choices| Source::Menu(path.to_string(), choices));
+    }
+
+    if file.is_file() {
+        if is_zip(file) {
+            return open_zip(file, None);
+        }
+
+        let mut data = Vec::new();
+        File::open(file).and_then(|mut f| f.read_to_end(&mut data)).map_err(|e| format!("{}: {}", path, e))?;
+
+        return Ok(Source::Rom(Rom {
+            name: path.to_string(),
+            path: path.to_string(),
+            data: data
+        }));
+    }
+
+    // a path through an archive
+    for archive in file.ancestors().skip(1) {
+        if archive.is_file() && is_zip(archive) {
+            let entry = file.strip_prefix(archive).unwrap().to_string_lossy().replace('\\', "/");
+            return open_zip(archive, Some(&entry));
+        }
+    }
+
+    Err(format!("{}: No such file or directory", path))
+}
+
+/// Open an entry picked from a listing
+pub fn open_choice(choice: &Choice) -> Result<Source, String> {
+    match choice.entry {
+        Some(ref entry) => open_zip(&choice.path, Some(entry)),
+        None => open(&choice.path.to_string_lossy())
+    }
+}
+
+/// Read the rom `entry` of an archive. Without an entry, the archive's only rom is read, or its
+/// roms are listed if it has several
+fn open_zip(path: &Path, entry: Option<&str>) -> Result<Source, String> {
+    let error = |e: &::std::fmt::Display| format!("{}: {}", path.display(), e);
+
+    let mut archive = File::open(path).map_err(|e| error(&e))
+        .and_then(|file| ZipArchive::new(file).map_err(|e| error(&e)))?;
+
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|e| error(&e))?;
+
+        if !file.is_dir() {
+            files.push(file.name().to_string());
+        }
+    }
+
+    // list every file if none of them looks like a rom
+    let roms: Vec<String> = files.iter().filter(|name| is_rom(Path::new(name))).cloned().collect();
+    let roms = if roms.is_empty() { files } else { roms };
+
+    let name = match entry {
+        Some(entry) => roms.iter().find(|name| *name == entry).cloned().ok_or_else(|| error(&format!("No rom named {}", entry)))?,
+        None if roms.len() == 1 => roms[0].clone(),
+        None => {
+            let choices = roms.iter()
+                .map(|name| Choice { label: name.clone(), path: path.to_path_buf(), entry: Some(name.clone()) })
+                .collect();
+
+            return Ok(Source::Menu(path.display().to_string(), choices));
+        }
+    };
+
+    let mut data = Vec::new();
+    archive.by_name(&name).map_err(|e| error(&e))?.read_to_end(&mut data).map_err(|e| error(&e))?;
+
+    // files saved for the rom go next to the archive
+    let file_name = Path::new(&name).file_name().map_or(name.clone(), |file_name| file_name.to_string_lossy().into_owned());
+
+    Ok(Source::Rom(Rom {
+        name: file_name.clone(),
+        path: path.with_file_name(&file_name).to_string_lossy().into_owned(),
+        data: data
+    }))
+}
+
+/// The parent directory, subdirectories, roms and archives of a directory, sorted by name
+fn list_directory(dir: &Path) -> Result<Vec<Choice>, String> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            dirs.push(Choice { label: format!("{}/", name), path: path, entry: None });
+        }
+        else if is_rom(&path) || is_zip(&path) {
+            files.push(Choice { label: name, path: path, entry: None });
+        }
+    }
+
+    dirs.sort_by_key(|choice| choice.label.to_lowercase());
+    files.sort_by_key(|choice| choice.label.to_lowercase());
+
+    let mut choices = Vec::new();
+
+    if let Some(parent) = fs::canonicalize(dir).ok().and_then(|dir| dir.parent().map(|parent| parent.to_path_buf())) {
+        choices.push(Choice { label: "../".to_string(), path: parent, entry: None });
+    }
+
+    choices.extend(dirs);
+    choices.extend(files);
+
+    Ok(choices)
+}
+
+fn is_rom(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => ROM_EXTENSIONS.iter().any(|rom_ext| ext.to_string_lossy().eq_ignore_ascii_case(rom_ext)),
+        None => true
+    }
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("zip"))
+}
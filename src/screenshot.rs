@@ -0,0 +1,97 @@
+//! Saving the display to image files
+
+use libchip8::Chip8;
+use png::{self, HasParameters};
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Save the display as a PNG enlarged `scale` times, or as a plain PBM with lit pixels as 1 if the
+/// file name ends in `.pbm`
+pub fn save(vm: &Chip8, file: &str, scale: usize) -> Result<(), Box<Error>> {
+    let is_pbm = Path::new(file).extension().map_or(false, |ext| ext.eq_ignore_ascii_case("pbm"));
+
+    if is_pbm {
+        write_pbm(vm, BufWriter::new(File::create(file)?))
+    }
+    else {
+        save_png(file, vm.get_display_memory(), vm.get_display_width(), vm.get_display_height(), scale)
+    }
+}
+
+/// Save RGB24 pixels as a PNG enlarged `scale` times
+pub fn save_png(file: &str, pixels: &[u8], width: usize, height: usize, scale: usize) -> Result<(), Box<Error>> {
+    let scale = scale.max(1);
+    let writer = BufWriter::new(File::create(file)?);
+
+    let mut encoder = png::Encoder::new(writer, (width * scale) as u32, (height * scale) as u32);
+    encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&enlarge(pixels, width, 3, scale))?;
+
+    Ok(())
+}
+
+/// Enlarge an image with `width` pixels of `pixel_size` bytes per row, repeating every pixel
+/// `scale` times in both directions
+pub fn enlarge(pixels: &[u8], width: usize, pixel_size: usize, scale: usize) -> Vec<u8> {
+    if scale <= 1 {
+        return pixels.to_vec();
+    }
+
+    let mut image = Vec::with_capacity(pixels.len() * scale * scale);
+
+    for row in pixels.chunks(width * pixel_size) {
+        let mut scaled_row = Vec::with_capacity(row.len() * scale);
+
+        for pixel in row.chunks(pixel_size) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+
+        for _ in 0..scale {
+            image.extend_from_slice(&scaled_row);
+        }
+    }
+
+    image
+}
+
+/// First `<rom file>-<n>.<extension>` that does not exist yet, for captures taken with a hotkey
+pub fn capture_file(rom_file: &str, extension: &str) -> String {
+    (1..).map(|n| format!("{}-{}.{}", rom_file, n, extension))
+        .find(|file| !Path::new(file).exists())
+        .unwrap()
+}
+
+fn write_pbm<W: Write>(vm: &Chip8, mut writer: W) -> Result<(), Box<Error>> {
+    let width = vm.get_display_width();
+    let height = vm.get_display_height();
+
+    writeln!(writer, "P1\n{} {}", width, height)?;
+
+    for y in 0..height {
+        let row: Vec<&str> = (0..width).map(|x| if vm.get_pixel_value(x, y) != 0 { "1" } else { "0" }).collect();
+        writeln!(writer, "{}", row.join(" "))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enlarge() {
+        let pixels = [1, 2, 3, 4];
+
+        assert_eq!(enlarge(&pixels, 2, 1, 2), vec![1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]);
+        assert_eq!(enlarge(&pixels, 1, 2, 2), vec![1, 2, 1, 2, 1, 2, 1, 2, 3, 4, 3, 4, 3, 4, 3, 4]);
+        assert_eq!(enlarge(&pixels, 2, 1, 1), pixels.to_vec());
+    }
+}